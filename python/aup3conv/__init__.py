@@ -1,4 +1,14 @@
-from ._aup3conv import open
+from ._aup3conv import (
+    open, create, salvage, compact, scan,
+    Label, LabelTrack, CheckReport, CheckIssue, SalvageReport, CompactReport,
+    LabelReport, LabelFinding, Scan, ScanResult, ProjectAttributes, Compatibility,
+    WaveTrack, RealtimeEffect, NoteTrack, TimeTrack,
+)
 
 
-__all__ = ["open"]
+__all__ = [
+    "open", "create", "salvage", "compact", "scan",
+    "Label", "LabelTrack", "CheckReport", "CheckIssue", "SalvageReport", "CompactReport",
+    "LabelReport", "LabelFinding", "Scan", "ScanResult", "ProjectAttributes", "Compatibility",
+    "WaveTrack", "RealtimeEffect", "NoteTrack", "TimeTrack",
+]
@@ -0,0 +1,62 @@
+"""Command line interface of aup3conv."""
+
+import argparse
+import sys
+
+from . import open as open_project, scan as scan_projects
+
+
+def check(args: argparse.Namespace) -> int:
+    status = 0
+    for path in args.paths:
+        try:
+            report = open_project(path, mode="snapshot" if args.snapshot else "readonly").check()
+        except Exception as err:
+            print(f"{path}: cannot open: {err}")
+            status = 1
+            continue
+
+        if report.ok:
+            print(f"{path}: ok ({report.blocks_checked} blocks)")
+            continue
+
+        status = 1
+        print(f"{path}: {len(report.issues)} issue(s)")
+        for issue in report.issues:
+            print(f"  {issue.kind}: clip={issue.clip_index} block={issue.block_id}: {issue.message}")
+    return status
+
+
+def scan(args: argparse.Namespace) -> int:
+    status = 0
+    for result in scan_projects(args.root, args.pattern, args.workers):
+        if result.ok:
+            print(f"{result.path}\t{result.rate}\t{result.duration:.3f}\t{result.tracks}\t{result.labels}")
+        else:
+            print(f"{result.path}: cannot open: {result.error}", file=sys.stderr)
+            status = 1
+    return status
+
+
+def main(argv: list[str] | None = None) -> int:
+    parser = argparse.ArgumentParser(prog="aup3conv")
+    commands = parser.add_subparsers(dest="command", required=True)
+
+    cmd = commands.add_parser("check", help="Verify the integrity of project files.")
+    cmd.add_argument("paths", nargs="+", help="Paths to .aup3 files.")
+    cmd.add_argument("--snapshot", action="store_true",
+                     help="Read from a consistent snapshot (for projects open in Audacity).")
+    cmd.set_defaults(func=check)
+
+    cmd = commands.add_parser("scan", help="Summarize all projects below a directory.")
+    cmd.add_argument("root", help="Directory to search.")
+    cmd.add_argument("--pattern", default="**/*.aup3", help="Glob pattern relative to root.")
+    cmd.add_argument("--workers", type=int, default=None, help="Number of threads.")
+    cmd.set_defaults(func=scan)
+
+    args = parser.parse_args(argv)
+    return args.func(args)
+
+
+if __name__ == "__main__":
+    sys.exit(main())
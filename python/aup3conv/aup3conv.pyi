@@ -1,9 +1,62 @@
-from _aup3conv import Project, Label
+from collections.abc import Sequence
+from typing import Literal
 
+from _aup3conv import Project, Label, CheckReport, CheckIssue, SalvageReport, CompactReport, Scan
 
-__all__ = ["open", "get_labels"]
 
+__all__ = ["open", "create", "salvage", "compact", "scan", "get_labels"]
 
-def open(path: str) -> Project:
-    """Open Audacity project file."""
+
+def open(path: str, mode: Literal["readonly", "immutable", "snapshot", "readwrite"] = "readonly",
+         source: Literal["saved", "autosave", "latest"] = "saved") -> Project:
+    """Open Audacity project file.
+
+    Use "snapshot" for projects that are currently open in Audacity. It
+    reads a consistent in-memory copy of the database, including changes
+    not yet checkpointed from the WAL. "immutable" ignores locks and the
+    WAL altogether. "readwrite" allows editing `Project.metadata`; do not
+    use it on projects that are open in Audacity.
+
+    `source` selects the document to decode. "autosave" reads the unsaved
+    state Audacity keeps after edits or a crash, "latest" prefers it over
+    the saved document whenever it exists.
+
+    Emits a `UserWarning` for each reason in `Project.compatibility`
+    why the project may not be read completely.
+    """
+    ...
+
+
+def create(path: str, rate: int, tracks: list[Sequence[float]],
+           labels: list[Label] | None = None, track_rates: list[int] | None = None) -> None:
+    """Create a new Audacity project file from mono audio tracks.
+
+    `rate` is the project rate. `track_rates` gives the rate of each
+    track and defaults to `rate` for all of them.
+    """
+    ...
+
+
+def salvage(path: str, out_dir: str) -> SalvageReport:
+    """Recover audio and labels from a damaged project into `out_dir`.
+
+    Writes one WAV file per recovered clip, unreferenced blocks in block
+    id order, and reachable labels in Audacity's label text format.
+    """
+    ...
+
+
+def compact(src: str, dst: str) -> CompactReport:
+    """Copy `src` to `dst` keeping only the blocks its saved document uses."""
+    ...
+
+
+def scan(root: str, pattern: str = "**/*.aup3", workers: int | None = None) -> Scan:
+    """Summarize all projects below `root` matching `pattern`.
+
+    Projects are opened on `workers` threads (default: number of CPUs)
+    without holding the GIL. The returned iterator yields a `ScanResult`
+    per file in order of completion. Files that cannot be opened yield
+    a result with `error` set instead of raising.
+    """
     ...
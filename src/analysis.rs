@@ -0,0 +1,226 @@
+//! Consistency analysis of label annotations.
+
+use std::collections::{BTreeMap, HashSet};
+
+use pyo3::prelude::*;
+
+use crate::structure::{Label, WaveClip};
+
+
+#[derive(Debug, Clone)]
+#[pyclass]
+pub struct LabelFinding {
+    /// One of "overlap", "duplicate", "zero_length", "beyond_audio",
+    /// "in_gap" and "unknown_title".
+    #[pyo3(get)]
+    pub kind: String,
+
+    #[pyo3(get)]
+    pub labels: Vec<Label>,
+
+    #[pyo3(get)]
+    pub message: String,
+}
+
+impl LabelFinding {
+    fn new(kind: &str, labels: Vec<Label>, message: String) -> Self {
+        Self { kind: kind.to_string(), labels, message }
+    }
+}
+
+#[pymethods]
+impl LabelFinding {
+    fn __str__(&self) -> String {
+        format!("LabelFinding(kind='{}', labels={}, message='{}')",
+            self.kind, self.labels.len(), self.message)
+    }
+
+    fn __repr__(&self) -> String {
+        self.__str__()
+    }
+}
+
+
+#[derive(Debug, Clone)]
+#[pyclass]
+pub struct LabelReport {
+    #[pyo3(get)]
+    pub findings: Vec<LabelFinding>,
+}
+
+#[pymethods]
+impl LabelReport {
+    #[getter]
+    pub fn ok(&self) -> bool {
+        self.findings.is_empty()
+    }
+
+    fn __str__(&self) -> String {
+        format!("LabelReport(ok={}, findings={})", self.ok(), self.findings.len())
+    }
+
+    fn __repr__(&self) -> String {
+        self.__str__()
+    }
+}
+
+
+/// Options of `analyze`.
+#[derive(Debug, Clone, Default)]
+pub struct AnalysisOptions {
+    /// Report point labels as zero-length regions.
+    pub expect_regions: bool,
+
+    /// Allowed label titles. `None` accepts every title.
+    pub vocabulary: Option<HashSet<String>>,
+}
+
+
+/// Time span `[start, stop)` covered by audible audio.
+///
+/// Each clip covers `play_start` to `play_end`, so trimmed audio and
+/// the stretch of a clip are taken into account.
+pub fn clip_extents(clips: &[WaveClip]) -> Vec<(f64, f64)> {
+    let mut out: Vec<(f64, f64)> = clips.iter()
+        .filter(|clip| clip.sequences.is_some())
+        .map(|clip| (clip.play_start(), clip.play_end()))
+        .filter(|(start, stop)| stop > start)
+        .collect();
+    out.sort_by(|x, y| x.0.total_cmp(&y.0));
+    out
+}
+
+
+/// Analyze `labels` for annotation problems.
+///
+/// `extents` are the time spans covered by audio, as returned by
+/// `clip_extents`.
+pub fn analyze(labels: &[Label], extents: &[(f64, f64)], options: &AnalysisOptions) -> LabelReport {
+    let mut findings = Vec::<LabelFinding>::new();
+
+    let mut tracks = BTreeMap::<Option<&str>, Vec<&Label>>::new();
+    for label in labels {
+        tracks.entry(label.track.as_deref()).or_default().push(label);
+    }
+
+    for track in tracks.values_mut() {
+        track.sort_by(|x, y| x.t.total_cmp(&y.t).then(x.t1.total_cmp(&y.t1)));
+        for (i, a) in track.iter().enumerate() {
+            for b in track[i + 1..].iter() {
+                if b.t >= a.t1 && !(a.is_point() && b.t == a.t) {
+                    break;
+                }
+                if a.t == b.t && a.t1 == b.t1 && a.title == b.title {
+                    findings.push(LabelFinding::new("duplicate", vec![(*a).clone(), (*b).clone()],
+                        format!("'{}' at {}-{} appears twice", a.title, a.t, a.t1)));
+                } else if !a.is_point() && !b.is_point() && b.t < a.t1 {
+                    findings.push(LabelFinding::new("overlap", vec![(*a).clone(), (*b).clone()],
+                        format!("'{}' ({}-{}) overlaps '{}' ({}-{})",
+                            a.title, a.t, a.t1, b.title, b.t, b.t1)));
+                }
+            }
+        }
+    }
+
+    let end = extents.iter().map(|x| x.1).fold(0f64, f64::max);
+    for label in labels {
+        if options.expect_regions && label.is_point() {
+            findings.push(LabelFinding::new("zero_length", vec![label.clone()],
+                format!("'{}' at {} has no duration", label.title, label.t)));
+        }
+
+        if label.t1 > end {
+            findings.push(LabelFinding::new("beyond_audio", vec![label.clone()],
+                format!("'{}' ends at {}, audio ends at {}", label.title, label.t1, end)));
+        } else if !covered(extents, label.t, label.t1) {
+            findings.push(LabelFinding::new("in_gap", vec![label.clone()],
+                format!("'{}' ({}-{}) lies partly outside of clips", label.title, label.t, label.t1)));
+        }
+
+        if let Some(vocabulary) = &options.vocabulary {
+            if !vocabulary.contains(&label.title) {
+                findings.push(LabelFinding::new("unknown_title", vec![label.clone()],
+                    format!("'{}' is not in the vocabulary", label.title)));
+            }
+        }
+    }
+
+    LabelReport { findings }
+}
+
+
+// Check whether audio exists everywhere in `[t, t1]`. Clip ends count
+// as covered, so adjacent clips leave no gap.
+fn covered(extents: &[(f64, f64)], t: f64, t1: f64) -> bool {
+    let mut sorted = extents.to_vec();
+    sorted.sort_by(|x, y| x.0.total_cmp(&y.0));
+
+    let mut reached = t;
+    for (start, stop) in sorted {
+        if start > reached {
+            break;
+        }
+        if stop >= reached {
+            reached = stop;
+            if reached >= t1 {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn label(t: f64, t1: f64, title: &str) -> Label {
+        Label::new(t, t1, title.to_string()).in_track(Some("a".to_string()), 0)
+    }
+
+    fn kinds(report: &LabelReport) -> Vec<&str> {
+        report.findings.iter().map(|x| x.kind.as_str()).collect()
+    }
+
+    #[test]
+    fn overlaps_and_duplicates() {
+        let labels = vec![label(0f64, 2f64, "x"), label(1f64, 3f64, "y"),
+            label(5f64, 6f64, "z"), label(5f64, 6f64, "z"), label(7f64, 7f64, "p")];
+        let report = analyze(&labels, &[(0f64, 10f64)], &AnalysisOptions::default());
+        assert_eq!(kinds(&report), vec!["overlap", "duplicate"]);
+    }
+
+    #[test]
+    fn extents_and_vocabulary() {
+        let labels = vec![label(0f64, 1f64, "x"), label(4f64, 5f64, "y"),
+            label(8f64, 12f64, "x"), label(2f64, 2f64, "x")];
+        let options = AnalysisOptions {
+            expect_regions: true,
+            vocabulary: Some(HashSet::from(["x".to_string()])),
+        };
+        let report = analyze(&labels, &[(0f64, 3f64), (6f64, 10f64)], &options);
+        assert_eq!(kinds(&report), vec!["in_gap", "unknown_title", "beyond_audio", "zero_length"]);
+    }
+
+    #[test]
+    fn spans_gap() {
+        let extents = [(0f64, 3f64), (6f64, 10f64)];
+        let labels = vec![label(2f64, 7f64, "x"), label(1f64, 1f64, "y")];
+        let report = analyze(&labels, &extents, &AnalysisOptions::default());
+        assert_eq!(kinds(&report), vec!["in_gap"]);
+        assert_eq!(report.findings[0].labels[0].title, "x");
+
+        // adjacent clips leave no gap
+        let report = analyze(&labels, &[(3f64, 10f64), (0f64, 3f64)], &AnalysisOptions::default());
+        assert!(report.findings.is_empty());
+    }
+
+    #[test]
+    fn nan_times() {
+        let labels = vec![label(1f64, 2f64, "x"), label(f64::NAN, 1f64, "y"), label(0f64, 1f64, "z")];
+        let report = analyze(&labels, &[(0f64, 3f64)], &AnalysisOptions::default());
+        assert_eq!(kinds(&report), vec!["in_gap"]);
+        assert_eq!(report.findings[0].labels[0].title, "y");
+    }
+}
@@ -3,3 +3,6 @@ pub mod fields;
 pub mod tagdict;
 pub mod projectdoc;
 pub mod audio;
+pub mod encoder;
+pub mod serializer;
+pub mod sampleblock;
@@ -7,6 +7,9 @@ pub enum AudioError {
     NoWaveblocks,
     ReadFailed,
     SeekFailed,
+    OutOfRange,
+    RateMismatch,
+    MultipleClips,
 }
 
 
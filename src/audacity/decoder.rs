@@ -1,111 +1,114 @@
-use rusqlite::blob::Blob;
-use std::io::Read;
+use std::io::{Error, ErrorKind, Read, Result};
+use byteorder::{LittleEndian, ReadBytesExt};
 
 
+/// Primitive values of the project blobs. Audacity writes them in
+/// little-endian byte order, whatever the architecture.
 pub trait Decoder {
-    fn byte(&mut self) -> u8;
-    fn nbytes(&mut self, size: usize) -> Vec<u8>;
-    fn short(&mut self) -> i16;
-    fn integer(&mut self) -> i32;
-    fn longlong(&mut self) -> i64;
-    fn double(&mut self) -> f64;
-    fn string(&mut self, size: usize, width: u8) -> String;
-    fn field_type_code(&mut self) -> u8;
+    fn byte(&mut self) -> Result<u8>;
+    fn nbytes(&mut self, size: usize) -> Result<Vec<u8>>;
+    fn short(&mut self) -> Result<i16>;
+    fn integer(&mut self) -> Result<i32>;
+    fn longlong(&mut self) -> Result<i64>;
+    fn float(&mut self) -> Result<f32>;
+    fn double(&mut self) -> Result<f64>;
+    fn string(&mut self, size: usize, width: u8) -> Result<String>;
+    fn field_type_code(&mut self) -> Result<u8>;
 }
 
 
-impl Decoder for Blob<'_> {
+impl<R: Read> Decoder for R {
 
-    fn byte(&mut self) -> u8 {
-        let mut buffer = [0u8; 1];
-        match self.read_exact(&mut buffer) {
-            Ok(()) => buffer[0],
-            Err(error) => panic!("Failed to read byte: {}", error),
-        }
+    fn byte(&mut self) -> Result<u8> {
+        self.read_u8()
     }
 
-    fn nbytes(&mut self, size: usize) -> Vec<u8> {
+    fn nbytes(&mut self, size: usize) -> Result<Vec<u8>> {
         let mut buffer = vec![0u8; size];
-        match self.read_exact(&mut buffer) {
-            Ok(()) => buffer,
-            Err(error) => panic!("Failed to read {} bytes: {}", size, error),
-        }
+        self.read_exact(&mut buffer)?;
+        Ok(buffer)
     }
 
-    fn short(&mut self) -> i16 {
-        let mut buffer = [0u8; 2];
-        if let Err(error) = self.read_exact(&mut buffer) {
-            panic!("LIB ERROR: Could not read bytes: {}", error);
-        }
-        let (pre, val, post) = unsafe { buffer.align_to::<i16>() };
-        assert_eq!(pre, []);
-        assert_eq!(post, []);
-        val[0]
+    fn short(&mut self) -> Result<i16> {
+        self.read_i16::<LittleEndian>()
     }
 
-    fn integer(&mut self) -> i32 {
-        let mut buffer = [0u8; 4];
-        if let Err(error) = self.read_exact(&mut buffer) {
-            panic!("LIB ERROR: Could not read bytes: {}", error);
-        }
-        let (pre, val, post) = unsafe { buffer.align_to::<i32>() };
-        assert_eq!(pre, []);
-        assert_eq!(post, []);
-        val[0]
+    fn integer(&mut self) -> Result<i32> {
+        self.read_i32::<LittleEndian>()
     }
 
-    fn longlong(&mut self) -> i64 {
-        let mut buffer = [0u8; 8];
-        if let Err(error) = self.read_exact(&mut buffer) {
-            panic!("LIB ERROR: Could not read bytes: {}", error);
-        }
-        let (pre, val, post) = unsafe { buffer.align_to::<i64>() };
-        assert_eq!(pre, []);
-        assert_eq!(post, []);
-        val[0]
+    fn longlong(&mut self) -> Result<i64> {
+        self.read_i64::<LittleEndian>()
     }
 
-    fn double(&mut self) -> f64 {
-        let mut buffer = [0u8; 8];
-        if let Err(error) = self.read_exact(&mut buffer) {
-            panic!("LIB ERROR: Could not read bytes: {}", error);
-        }
-        let (pre, val, post) = unsafe { buffer.align_to::<f64>() };
-        assert_eq!(pre, []);
-        assert_eq!(post, []);
+    fn float(&mut self) -> Result<f32> {
+        self.read_f32::<LittleEndian>()
+    }
 
-        let _digits = self.integer();
-        val[0]
+    fn double(&mut self) -> Result<f64> {
+        self.read_f64::<LittleEndian>()
     }
 
-    fn string(&mut self, size: usize, width: u8) -> String {
-        let buffer = self.nbytes(size);
-        
-        match width {
-            1 => {
-                String::from_utf8(buffer).unwrap()
-            },
+    fn string(&mut self, size: usize, width: u8) -> Result<String> {
+        let buffer = self.nbytes(size)?;
+        if width == 0 || !size.is_multiple_of(width as usize) {
+            return Err(invalid(format!("Bad string of {} bytes with char size {}", size, width)));
+        }
 
-            2 => { 
-                let (pre, buf, post) = unsafe { buffer.align_to::<u16>() };
-                assert_eq!(pre, []);
-                assert_eq!(post, []);
-                String::from_utf16(&buf).unwrap()
-            },
-            4 => {
-                let mut out = String::new();
-                let (pre, buf, post) = unsafe { buffer.align_to::<char>() };
-                assert_eq!(pre, []);
-                assert_eq!(post, []);
-                out.extend(buf.iter());
-                out
+        match width {
+            1 => String::from_utf8(buffer).map_err(invalid),
+            2 => {
+                let units: Vec<u16> = buffer.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+                String::from_utf16(&units).map_err(invalid)
             },
-
-            _ => panic!("Bad char size: {}", width)
+            4 => buffer.chunks_exact(4)
+                .map(|c| char::from_u32(u32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+                    .ok_or_else(|| invalid("Bad UTF-32 character")))
+                .collect(),
+            _ => Err(invalid(format!("Bad char size: {}", width)))
         }
     }
 
-    fn field_type_code(&mut self) -> u8 {
+    fn field_type_code(&mut self) -> Result<u8> {
         self.byte()
     }
 }
+
+
+/// Error for malformed contents of a blob.
+pub fn invalid<E>(error: E) -> Error
+where E: Into<Box<dyn std::error::Error + Send + Sync>> {
+    Error::new(ErrorKind::InvalidData, error)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn little_endian() {
+        let bytes = [0x34u8, 0x12, 0x78, 0x56, 0x34, 0x12, 0, 0, 0, 0, 0, 0, 0xf0, 0x3f];
+        let mut blob = &bytes[..];
+        assert_eq!(blob.short().unwrap(), 0x1234);
+        assert_eq!(blob.integer().unwrap(), 0x12345678);
+        assert_eq!(blob.double().unwrap(), 1f64);
+        assert_eq!(blob.byte().unwrap_err().kind(), ErrorKind::UnexpectedEof);
+
+        // values need not be aligned
+        let mut odd = &[0u8, 0x34, 0x12][1..];
+        assert_eq!(odd.short().unwrap(), 0x1234);
+    }
+
+    #[test]
+    fn strings() {
+        let mut blob = &[b'a', 0, 0xe4, 0, b'a', 0, 0, 0, 0xe4, 0, 0, 0][..];
+        assert_eq!(blob.string(4, 2).unwrap(), "aä");
+        assert_eq!(blob.string(8, 4).unwrap(), "aä");
+
+        assert_eq!((&[0xffu8][..]).string(1, 1).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!((&[0u8; 3][..]).string(3, 2).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!((&[0u8; 4][..]).string(4, 3).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!((&[0xffu8; 4][..]).string(4, 4).unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,75 @@
+use byteorder::{LittleEndian, WriteBytesExt};
+
+
+pub trait Encoder {
+    fn byte(&mut self, value: u8);
+    fn short(&mut self, value: i16);
+    fn integer(&mut self, value: i32);
+    fn longlong(&mut self, value: i64);
+    fn float(&mut self, value: f32, digits: i32);
+    fn double(&mut self, value: f64, digits: i32);
+    fn string(&mut self, value: &str, width: u8);
+    fn field_type_code(&mut self, code: u8);
+}
+
+
+impl Encoder for Vec<u8> {
+
+    fn byte(&mut self, value: u8) {
+        self.push(value);
+    }
+
+    fn short(&mut self, value: i16) {
+        self.write_i16::<LittleEndian>(value).unwrap();
+    }
+
+    fn integer(&mut self, value: i32) {
+        self.write_i32::<LittleEndian>(value).unwrap();
+    }
+
+    fn longlong(&mut self, value: i64) {
+        self.write_i64::<LittleEndian>(value).unwrap();
+    }
+
+    fn float(&mut self, value: f32, digits: i32) {
+        self.write_f32::<LittleEndian>(value).unwrap();
+        self.integer(digits);
+    }
+
+    fn double(&mut self, value: f64, digits: i32) {
+        self.write_f64::<LittleEndian>(value).unwrap();
+        self.integer(digits);
+    }
+
+    fn string(&mut self, value: &str, width: u8) {
+        match width {
+            1 => self.extend_from_slice(value.as_bytes()),
+            2 => {
+                for unit in value.encode_utf16() {
+                    self.write_u16::<LittleEndian>(unit).unwrap();
+                }
+            },
+            4 => {
+                for ch in value.chars() {
+                    self.write_u32::<LittleEndian>(ch as u32).unwrap();
+                }
+            },
+            _ => panic!("Bad char size: {}", width)
+        }
+    }
+
+    fn field_type_code(&mut self, code: u8) {
+        self.byte(code);
+    }
+}
+
+
+/// Number of bytes `value` occupies when encoded with char size `width`.
+pub fn encoded_len(value: &str, width: u8) -> usize {
+    match width {
+        1 => value.len(),
+        2 => value.encode_utf16().count() * 2,
+        4 => value.chars().count() * 4,
+        _ => panic!("Bad char size: {}", width)
+    }
+}
@@ -1,6 +1,7 @@
+use std::io::Result;
 use rusqlite::blob::Blob;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum FieldType {
     CharSize { value: u8 },
     StartTag { id: i16 },
@@ -11,8 +12,8 @@ pub enum FieldType {
     Long { id: i16, value: i32 },
     LongLong { id: i16, value: i64},
     SizeT { id: i16, value: usize },
-    Float { id: i16, value: f32, digits: u8 },
-    Double { id: i16, value: f64, digits: u8 },
+    Float { id: i16, value: f32, digits: i32 },
+    Double { id: i16, value: f64, digits: i32 },
     Data { size: i32, value: String },
     Raw { size: i32, value: String },
     Push,
@@ -22,23 +23,23 @@ pub enum FieldType {
 
 
 pub trait ReadDocField {
-    fn read_field(&self, blob: &mut Blob) -> FieldType;
-    fn char_size(&self, blob: &mut Blob) -> FieldType;
-    fn start_tag(&self, blob: &mut Blob) -> FieldType;
-    fn end_tag(&self, blob: &mut Blob) -> FieldType;
-    fn str(&self, blob: &mut Blob) -> FieldType;
-    fn integer(&self, blob: &mut Blob) -> FieldType;
-    fn boolean(&self, blob: &mut Blob) -> FieldType;
-    fn long(&self, blob: &mut Blob) -> FieldType;
-    fn longlong(&self, blob: &mut Blob) -> FieldType;
-    fn size_t(&self, blob: &mut Blob) -> FieldType;
-    fn float(&self, blob: &mut Blob) -> FieldType;
-    fn double(&self, blob: &mut Blob) -> FieldType;
-    fn data(&self, blob: &mut Blob) -> FieldType;
-    fn raw(&self, blob: &mut Blob) -> FieldType;
-    fn push(&self, blob: &mut Blob) -> FieldType;
-    fn pop(&self, blob: &mut Blob) -> FieldType;
-    fn name(&self, blob: &mut Blob) -> FieldType;
+    fn read_field(&self, blob: &mut Blob) -> Result<FieldType>;
+    fn char_size(&self, blob: &mut Blob) -> Result<FieldType>;
+    fn start_tag(&self, blob: &mut Blob) -> Result<FieldType>;
+    fn end_tag(&self, blob: &mut Blob) -> Result<FieldType>;
+    fn str(&self, blob: &mut Blob) -> Result<FieldType>;
+    fn integer(&self, blob: &mut Blob) -> Result<FieldType>;
+    fn boolean(&self, blob: &mut Blob) -> Result<FieldType>;
+    fn long(&self, blob: &mut Blob) -> Result<FieldType>;
+    fn longlong(&self, blob: &mut Blob) -> Result<FieldType>;
+    fn size_t(&self, blob: &mut Blob) -> Result<FieldType>;
+    fn float(&self, blob: &mut Blob) -> Result<FieldType>;
+    fn double(&self, blob: &mut Blob) -> Result<FieldType>;
+    fn data(&self, blob: &mut Blob) -> Result<FieldType>;
+    fn raw(&self, blob: &mut Blob) -> Result<FieldType>;
+    fn push(&self, blob: &mut Blob) -> Result<FieldType>;
+    fn pop(&self, blob: &mut Blob) -> Result<FieldType>;
+    fn name(&self, blob: &mut Blob) -> Result<FieldType>;
 }
 
 
@@ -47,7 +48,7 @@ pub trait CharSize {
 }
 
 pub trait ReadDictField: CharSize {
-    fn read_field(&self, blob: &mut Blob) -> FieldType;
-    fn char_size(&self, blob: &mut Blob) -> FieldType;
-    fn name(&self, blob: &mut Blob) -> FieldType;
+    fn read_field(&self, blob: &mut Blob) -> Result<FieldType>;
+    fn char_size(&self, blob: &mut Blob) -> Result<FieldType>;
+    fn name(&self, blob: &mut Blob) -> Result<FieldType>;
 }
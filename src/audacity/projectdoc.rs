@@ -1,12 +1,12 @@
 use std::io::Seek;
-use std::io::Result;
+use std::io::{Error, Result};
 use std::fmt::Display;
 use rusqlite::{Connection, DatabaseName};
 use rusqlite::blob::Blob;
 
 use crate::audacity::tagdict::TagDict;
 use crate::audacity::fields::{CharSize, FieldType, ReadDocField};
-use crate::audacity::decoder::Decoder;
+use crate::audacity::decoder::{invalid, Decoder};
 use crate::tagstack::{Tag, TagStack};
 use crate::structure::*;
 
@@ -33,8 +33,8 @@ impl CharSize for ProjectDocReader {
 
 
 impl ReadDocField for ProjectDocReader {
-    fn read_field(&self, blob: &mut Blob) -> FieldType {
-        let ftc = blob.field_type_code();
+    fn read_field(&self, blob: &mut Blob) -> Result<FieldType> {
+        let ftc = blob.field_type_code()?;
         match ftc {
              0 => self.char_size(blob),
              1 => self.start_tag(blob),
@@ -52,85 +52,89 @@ impl ReadDocField for ProjectDocReader {
             13 => self.push(blob),
             14 => self.pop(blob),
             15 => self.name(blob),
-             _ => panic!("Unknown field type code detected"),
+             code => Err(invalid(format!("Unknown field type code {}", code))),
         }
     }
 
-    fn char_size(&self, blob: &mut Blob) -> FieldType {
-        FieldType::CharSize { value: blob.byte() }
+    fn char_size(&self, blob: &mut Blob) -> Result<FieldType> {
+        Ok(FieldType::CharSize { value: blob.byte()? })
     }
 
-    fn start_tag(&self, blob: &mut Blob) -> FieldType {
-        let id = blob.short();
-        FieldType::StartTag { id: id }
+    fn start_tag(&self, blob: &mut Blob) -> Result<FieldType> {
+        let id = blob.short()?;
+        Ok(FieldType::StartTag { id: id })
     }
-    fn end_tag(&self, blob: &mut Blob) -> FieldType {
-        let id = blob.short();
-        FieldType::EndTag { id: id }
+    fn end_tag(&self, blob: &mut Blob) -> Result<FieldType> {
+        let id = blob.short()?;
+        Ok(FieldType::EndTag { id: id })
     }
 
-    fn str(&self, blob: &mut Blob) -> FieldType {
-        let id = blob.short();
-        let size = blob.integer();
-        FieldType::Str { id: id, size: size, value: blob.string(size as usize, self.chs()).clone() }
+    fn str(&self, blob: &mut Blob) -> Result<FieldType> {
+        let id = blob.short()?;
+        let size = blob.integer()?;
+        Ok(FieldType::Str { id: id, size: size, value: blob.string(size as usize, self.chs())? })
     }
 
-    fn integer(&self, blob: &mut Blob) -> FieldType {
-        FieldType::Int { id: blob.short(), value: blob.integer() }
+    fn integer(&self, blob: &mut Blob) -> Result<FieldType> {
+        Ok(FieldType::Int { id: blob.short()?, value: blob.integer()? })
     }
 
-    fn boolean(&self, blob: &mut Blob) -> FieldType {
-        let id = blob.short();
-        let value = match blob.byte() {
+    fn boolean(&self, blob: &mut Blob) -> Result<FieldType> {
+        let id = blob.short()?;
+        let value = match blob.byte()? {
             1 => true,
             0 => false,
-            _ => { panic!("Somethings is really wrong"); }
+            other => return Err(invalid(format!("Bad boolean value {}", other)))
         };
-        FieldType::Bool { id: id, value: value }
+        Ok(FieldType::Bool { id: id, value: value })
     }
 
-    fn long(&self, blob: &mut Blob) -> FieldType {
-        FieldType::Long { id: blob.short(), value: blob.integer() }
+    fn long(&self, blob: &mut Blob) -> Result<FieldType> {
+        Ok(FieldType::Long { id: blob.short()?, value: blob.integer()? })
     }
 
-    fn longlong(&self, blob: &mut Blob) -> FieldType {
-        FieldType::LongLong { id: blob.short(), value: blob.longlong() as i64 }
+    fn longlong(&self, blob: &mut Blob) -> Result<FieldType> {
+        Ok(FieldType::LongLong { id: blob.short()?, value: blob.longlong()? })
     }
 
-    fn size_t(&self, blob: &mut Blob) -> FieldType {
-        FieldType::SizeT { id: blob.short(), value: blob.integer() as usize }
+    fn size_t(&self, blob: &mut Blob) -> Result<FieldType> {
+        Ok(FieldType::SizeT { id: blob.short()?, value: blob.integer()? as usize })
     }
 
-    fn float(&self, blob: &mut Blob) -> FieldType {
-        FieldType::Float { id: blob.short(), value: blob.double() as f32, digits: 0 }
+    fn float(&self, blob: &mut Blob) -> Result<FieldType> {
+        let id = blob.short()?;
+        let value = blob.float()?;
+        Ok(FieldType::Float { id, value, digits: blob.integer()? })
     }
 
-    fn double(&self, blob: &mut Blob) -> FieldType {
-        FieldType::Double { id: blob.short(), value: blob.double(), digits: 0 }
+    fn double(&self, blob: &mut Blob) -> Result<FieldType> {
+        let id = blob.short()?;
+        let value = blob.double()?;
+        Ok(FieldType::Double { id, value, digits: blob.integer()? })
     }
 
-    fn data(&self, blob: &mut Blob) -> FieldType {
-        let size = blob.integer();
-        FieldType::Data { size: size, value: blob.string(size as usize, self.chs()).clone() }
+    fn data(&self, blob: &mut Blob) -> Result<FieldType> {
+        let size = blob.integer()?;
+        Ok(FieldType::Data { size: size, value: blob.string(size as usize, self.chs())? })
     }
 
-    fn raw(&self, blob: &mut Blob) -> FieldType {
-        let size = blob.integer();
-        FieldType::Raw { size: size, value: blob.string(size as usize, self.chs()).clone() }
+    fn raw(&self, blob: &mut Blob) -> Result<FieldType> {
+        let size = blob.integer()?;
+        Ok(FieldType::Raw { size: size, value: blob.string(size as usize, self.chs())? })
     }
 
-    fn push(&self, _blob: &mut Blob) -> FieldType {
-        FieldType::Push
+    fn push(&self, _blob: &mut Blob) -> Result<FieldType> {
+        Ok(FieldType::Push)
     }
 
-    fn pop(&self, _blob: &mut Blob) -> FieldType {
-        FieldType::Pop
+    fn pop(&self, _blob: &mut Blob) -> Result<FieldType> {
+        Ok(FieldType::Pop)
     }
 
-    fn name(&self, blob: &mut Blob) -> FieldType {
-        let id = blob.short();
-        let size = blob.short();
-        FieldType::Name { id: id, size: size, value: blob.string(size as usize, self.chs()) }
+    fn name(&self, blob: &mut Blob) -> Result<FieldType> {
+        let id = blob.short()?;
+        let size = blob.short()?;
+        Ok(FieldType::Name { id: id, size: size, value: blob.string(size as usize, self.chs())? })
     }
 }
 
@@ -154,24 +158,32 @@ impl ProjectDoc {
     }
 
     pub fn decode(&mut self, con: &Connection) -> Result<()>{
+        self.decode_from(con, "project")
+    }
+
+    // Decode the doc of `table`, which is either "project" or "autosave".
+    pub fn decode_from(&mut self, con: &Connection, table: &str) -> Result<()>{
 
-        let mut blob = con.blob_open(DatabaseName::Main, "project",
-            "doc", 1, true).expect("Failed to read blob");
+        let mut blob = con.blob_open(DatabaseName::Main, table,
+            "doc", 1, true).map_err(Error::other)?;
 
-        while (blob.stream_position().expect("Cannot read position") as usize) < blob.len() {
-            match self.reader.read_field(&mut blob) {
+        while (blob.stream_position()? as usize) < blob.len() {
+            match self.reader.read_field(&mut blob)? {
                 FieldType::CharSize { value } => { self.reader.char_size = value; },
-                FieldType::StartTag { id } => { self.add_tag(id); },
-                FieldType::EndTag { id: _ } => { self.tags.decrease_level(); },
-                FieldType::Str { id, size: _, value } => { self.add_attribute(id, value); },
-                FieldType::Int { id, value } => { self.add_attribute(id, value); },
-                FieldType::Bool { id, value } => { self.add_attribute(id, value); },
-                FieldType::Long { id, value } => { self.add_attribute(id, value); },
-                FieldType::LongLong { id, value } => { self.add_attribute(id, value); },
-                FieldType::SizeT { id, value } => { self.add_attribute(id, value); },
-                FieldType::Float { id, value, .. } => { self.add_attribute(id, value); },
-                FieldType::Double { id, value, .. } => { self.add_attribute(id, value); },
-                FieldType::Data { size: _, value: _ } => { panic!("FIELD TYPE <DATA> encountered"); },
+                FieldType::StartTag { id } => { self.add_tag(id)?; },
+                FieldType::EndTag { .. } if self.tags.current_level == 0 => {
+                    return Err(invalid("End tag without start tag"));
+                },
+                FieldType::EndTag { .. } => { self.tags.decrease_level(); },
+                FieldType::Str { id, size: _, value } => { self.add_attribute(id, value)?; },
+                FieldType::Int { id, value } => { self.add_attribute(id, value)?; },
+                FieldType::Bool { id, value } => { self.add_attribute(id, value)?; },
+                FieldType::Long { id, value } => { self.add_attribute(id, value)?; },
+                FieldType::LongLong { id, value } => { self.add_attribute(id, value)?; },
+                FieldType::SizeT { id, value } => { self.add_attribute(id, value)?; },
+                FieldType::Float { id, value, .. } => { self.add_attribute(id, value)?; },
+                FieldType::Double { id, value, .. } => { self.add_attribute(id, value)?; },
+                FieldType::Data { .. } => { return Err(invalid("Unexpected field type <DATA>")); },
                 FieldType::Raw { size: _, value } => { self.collect(value) },
                 FieldType::Push => { },
                 FieldType::Pop => { },
@@ -181,17 +193,52 @@ impl ProjectDoc {
         Ok(())
     }
 
-    fn add_tag(&mut self, id: i16) {
-        let name = self.tagdict.dict.get(&id).expect("wer");
-        self.tags.add_tag(name)
+    // Decode as many fields of `table` as possible.
+    //
+    // Stops at the first field that cannot be read and keeps the tags
+    // decoded up to that point. Returns `true` if the whole document
+    // was read.
+    pub fn decode_partial(&mut self, con: &Connection, table: &str) -> bool {
+        self.decode_from(con, table).is_ok()
+    }
+
+    // Read the raw field stream of the doc in `table` without building tags.
+    pub fn fields(&mut self, con: &Connection, table: &str) -> Result<Vec<FieldType>> {
+        let mut blob = con.blob_open(DatabaseName::Main, table,
+            "doc", 1, true).map_err(Error::other)?;
+
+        let mut out = Vec::<FieldType>::new();
+        while (blob.stream_position()? as usize) < blob.len() {
+            let field = self.reader.read_field(&mut blob)?;
+            if let FieldType::CharSize { value } = field {
+                self.reader.char_size = value;
+            }
+            out.push(field);
+        }
+        Ok(out)
+    }
+
+    pub fn chs(&self) -> u8 {
+        self.reader.chs()
     }
 
-    fn add_attribute<T: Display>(&mut self, id: i16, value: T) {
-        let name = self.tagdict.dict.get(&id).expect("Missing");
+    pub fn is_empty(&self) -> bool {
+        self.tags.stack.is_empty()
+    }
+
+    fn add_tag(&mut self, id: i16) -> Result<()> {
+        let name = self.tagdict.name(id)?;
+        self.tags.add_tag(name);
+        Ok(())
+    }
+
+    fn add_attribute<T: Display>(&mut self, id: i16, value: T) -> Result<()> {
+        let name = self.tagdict.name(id)?;
         match self.tags.stack.last_mut() {
             Some(tag) => tag.add_attribute(name, &value.to_string()),
-            None => panic!("WURST")
+            None => return Err(invalid(format!("Attribute '{}' outside of any tag", name)))
         }
+        Ok(())
     }
 
     fn collect(&mut self, value: String) {
@@ -200,8 +247,40 @@ impl ProjectDoc {
 
     pub fn parse_labels(&mut self) -> Result<Option<Vec<Label>>> {
         let mut out = Vec::<Label>::new();
+        let mut track: Option<String> = None;
+        let mut index = 0usize;
         for tag in self.tags.stack.iter() {
-            if tag.name == "label" { out.push(Label::from_tag(&tag)?) };
+            if tag.name == "labeltrack" {
+                track = tag.attributes.get("name").cloned();
+                index = 0;
+            }
+            else if tag.name == "label" {
+                out.push(Label::from_tag(tag)?.in_track(track.clone(), index));
+                index += 1;
+            };
+        }
+        if out.is_empty() {
+            return Ok(None)
+        }
+        Ok(Some(out))
+    }
+
+    pub fn parse_label_tracks(&mut self) -> Result<Option<Vec<LabelTrack>>> {
+        let mut out = Vec::<LabelTrack>::new();
+        for tag in self.tags.stack.iter() {
+            if tag.name == "labeltrack" {
+                out.push(LabelTrack::from_tag(tag)?)
+            }
+            else if tag.name == "label" {
+                match out.last_mut() {
+                    Some(track) => {
+                        let label = Label::from_tag(tag)?
+                            .in_track(Some(track.name.clone()), track.labels.len());
+                        track.labels.push(label);
+                    },
+                    None => return Err(invalid("Label outside of a labeltrack"))
+                }
+            };
         }
         if out.is_empty() {
             return Ok(None)
@@ -222,6 +301,146 @@ impl ProjectDoc {
         }
     }
 
+    // Name and value of each `tag` in the `tags` element, in document order.
+    pub fn parse_metadata(&mut self) -> Vec<(String, String)> {
+        self.tags.stack.iter()
+            .filter(|tag| tag.name == "tag")
+            .filter_map(|tag| Some((tag.attributes.get("name")?.clone(),
+                tag.attributes.get("value").cloned().unwrap_or_default())))
+            .collect()
+    }
+
+    pub fn parse_project_attributes(&mut self) -> Result<Option<ProjectAttributes>> {
+        match self.get_tag_by_name("project") {
+            Some(tag) => Ok(Some(ProjectAttributes::from_tag(tag)?)),
+            None => Ok(None)
+        }
+    }
+
+    pub fn parse_note_tracks(&mut self) -> Result<Vec<NoteTrack>> {
+        self.tags.stack.iter()
+            .filter(|tag| tag.name == "notetrack")
+            .map(NoteTrack::from_tag)
+            .collect()
+    }
+
+    // The time track with the control points of its envelope. Clips have
+    // envelopes as well, so only points within the time track count.
+    pub fn parse_time_track(&mut self) -> Result<Option<TimeTrack>> {
+        let mut out: Option<TimeTrack> = None;
+        let mut path = Vec::<&str>::new();
+        for (tag, level) in self.tags.stack.iter().zip(self.tags.level.iter()) {
+            path.truncate(*level as usize - 1);
+            path.push(tag.name.as_str());
+            match tag.name.as_str() {
+                "timetrack" if out.is_none() => out = Some(TimeTrack::from_tag(tag)?),
+                "controlpoint" if path.ends_with(&["timetrack", "envelope", "controlpoint"]) => {
+                    let num = |key: &str| tag.attributes.get(key).and_then(|val| val.parse::<f64>().ok());
+                    if let (Some(track), Some(t), Some(val)) = (out.as_mut(), num("t"), num("val")) {
+                        track.points.push((t, val));
+                    }
+                },
+                _ => {}
+            }
+        }
+        if let Some(track) = out.as_mut() {
+            track.points.sort_by(|x, y| x.0.total_cmp(&y.0));
+        }
+        Ok(out)
+    }
+
+    pub fn parse_wavetracks(&mut self) -> Result<Vec<WaveTrack>> {
+        Ok(self.parse_tracks_and_effects()?.0)
+    }
+
+    // Realtime effects of the master channel.
+    pub fn parse_master_effects(&mut self) -> Result<Vec<RealtimeEffect>> {
+        Ok(self.parse_tracks_and_effects()?.1)
+    }
+
+    // Wave tracks with their realtime effects, and the effects of the
+    // master channel. An `effects` element belongs to the element that
+    // encloses it: a wave track, or the project for the master channel.
+    // Effects on other kinds of tracks are skipped.
+    fn parse_tracks_and_effects(&self) -> Result<(Vec<WaveTrack>, Vec<RealtimeEffect>)> {
+        let mut tracks = Vec::<WaveTrack>::new();
+        let mut master = Vec::<RealtimeEffect>::new();
+        let mut path = Vec::<&str>::new();
+        let mut stack_active = true;
+        let mut target: Option<bool> = None;     // Some(true) for the master channel
+
+        for (tag, level) in self.tags.stack.iter().zip(self.tags.level.iter()) {
+            path.truncate(*level as usize - 1);
+            let parent = path.last().copied();
+            path.push(tag.name.as_str());
+
+            let effects = match target {
+                Some(true) => Some(&mut master),
+                Some(false) => tracks.last_mut().map(|track| &mut track.effects),
+                None => None,
+            };
+            match (tag.name.as_str(), parent) {
+                ("wavetrack", _) => tracks.push(WaveTrack::from_tag(tag)?),
+                ("effects", Some(owner)) => {
+                    target = match owner {
+                        "project" => Some(true),
+                        "wavetrack" => Some(false),
+                        _ => None
+                    };
+                    stack_active = tag.attributes.get("active").is_none_or(|x| parse_flag(x));
+                },
+                ("effect", Some("effects")) => if let Some(effects) = effects {
+                    let mut effect = RealtimeEffect::from_tag(tag)?;
+                    effect.active &= stack_active;
+                    effects.push(effect);
+                },
+                ("parameters", Some("effect")) => if let Some(effect) = effects.and_then(|x| x.last_mut()) {
+                    let mut attributes: Vec<(String, String)> = tag.attributes.iter()
+                        .map(|(key, value)| (key.clone(), value.clone())).collect();
+                    attributes.sort();
+                    effect.parameters.extend(attributes);
+                },
+                ("parameter", Some("parameters")) => if let Some(effect) = effects.and_then(|x| x.last_mut()) {
+                    if let Some(name) = tag.attributes.get("name") {
+                        let value = tag.attributes.get("value").cloned().unwrap_or_default();
+                        effect.parameters.push((name.clone(), value));
+                    }
+                },
+                _ => {}
+            }
+        }
+        Ok((tracks, master))
+    }
+
+    // Distinct element names in document order.
+    pub fn element_names(&self) -> Vec<String> {
+        let mut out = Vec::<String>::new();
+        for tag in self.tags.stack.iter() {
+            if !out.contains(&tag.name) {
+                out.push(tag.name.clone());
+            }
+        }
+        out
+    }
+
+    // Control points of the volume envelopes of clips.
+    pub fn clip_envelope_points(&self) -> usize {
+        let mut path = Vec::<&str>::new();
+        let mut count = 0;
+        for (tag, level) in self.tags.stack.iter().zip(self.tags.level.iter()) {
+            path.truncate(*level as usize - 1);
+            path.push(tag.name.as_str());
+            if path.ends_with(&["waveclip", "envelope", "controlpoint"]) {
+                count += 1;
+            }
+        }
+        count
+    }
+
+    pub fn parse_num_wavetracks(&mut self) -> usize {
+        self.tags.stack.iter().filter(|tag| tag.name == "wavetrack").count()
+    }
+
     pub fn parse_sequences(&mut self) -> Result<Option<Vec<Sequence>>> {
         let mut out = Vec::<Sequence>::new();
         for tag in self.tags.stack.iter() {
@@ -246,14 +465,22 @@ impl ProjectDoc {
 
     pub fn parse_waveclips(&mut self) -> Result<Option<Vec<WaveClip>>> {
         let mut out = Vec::<WaveClip>::new();
+        let mut rate: Option<u32> = None;
         for tag in self.tags.stack.iter() {
-            if tag.name == "waveclip" {
-                out.push(WaveClip::from_tag(&tag)?)
+            if tag.name == "wavetrack" {
+                rate = tag.attributes.get("rate")
+                    .and_then(|val| val.parse::<f64>().ok())
+                    .map(|val| val.round() as u32);
+            }
+            else if tag.name == "waveclip" {
+                let mut clip = WaveClip::from_tag(tag)?;
+                clip.rate = rate;
+                out.push(clip)
             }
             else if tag.name == "sequence" {
                 match out.last_mut() {
                     Some(clip) => { clip.sequences = Some(Sequence::from_tag(&tag)?) },
-                    None => panic!("No waveclip to append to")
+                    None => return Err(invalid("Sequence outside of a waveclip"))
                 }
             }
             else if tag.name == "waveblock" {
@@ -264,7 +491,7 @@ impl ProjectDoc {
                             let _ = clip.sequences.insert(seq);
                         }
                     },
-                    None => panic!("No waveclip to append to")
+                    None => return Err(invalid("Waveblock outside of a waveclip"))
                 }
 
             };
@@ -273,7 +500,7 @@ impl ProjectDoc {
             return Ok(None)
         }
 
-        out.sort_by(|x, y| x.offset.partial_cmp(&y.offset).unwrap());
+        out.sort_by(|x, y| x.offset.total_cmp(&y.offset));
         Ok(Some(out))
     }
 
@@ -296,9 +523,33 @@ mod tests {
     fn read_project_from_aup3() {
         let con = Connection::open("data/test-project.aup3").expect("open failed");
         let mut tagdict = TagDict::new();
-        tagdict.decode(&con);
+        tagdict.decode(&con).unwrap();
 
         let mut project = ProjectDoc::new(tagdict);
         let _ = project.decode(&con);
     }
+
+    #[test]
+    fn float_and_double_digits() {
+        let mut bytes = vec![9u8];
+        bytes.extend_from_slice(&3i16.to_le_bytes());
+        bytes.extend_from_slice(&0.5f32.to_le_bytes());
+        bytes.extend_from_slice(&7i32.to_le_bytes());
+        bytes.push(10);
+        bytes.extend_from_slice(&4i16.to_le_bytes());
+        bytes.extend_from_slice(&0.25f64.to_le_bytes());
+        bytes.extend_from_slice(&(-1i32).to_le_bytes());
+
+        let con = Connection::open_in_memory().unwrap();
+        con.execute("CREATE TABLE project (id INTEGER PRIMARY KEY, doc BLOB)", []).unwrap();
+        con.execute("INSERT INTO project (id, doc) VALUES (1, ?1)", [&bytes]).unwrap();
+        let mut blob = con.blob_open(DatabaseName::Main, "project", "doc", 1, true).unwrap();
+
+        let reader = ProjectDocReader::new(1);
+        assert!(matches!(reader.read_field(&mut blob).unwrap(),
+            FieldType::Float { id: 3, value, digits: 7 } if value == 0.5));
+        assert!(matches!(reader.read_field(&mut blob).unwrap(),
+            FieldType::Double { id: 4, value, digits: -1 } if value == 0.25));
+        assert_eq!(blob.stream_position().unwrap() as usize, bytes.len());
+    }
 }
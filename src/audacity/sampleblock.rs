@@ -0,0 +1,146 @@
+use byteorder::{ByteOrder, LittleEndian, WriteBytesExt};
+
+
+/// Audacity's `int16Sample` format code.
+pub const INT16_SAMPLE: u64 = 0x00020001;
+
+/// Audacity's `int24Sample` format code.
+pub const INT24_SAMPLE: u64 = 0x00040001;
+
+/// Audacity's `floatSample` format code.
+pub const FLOAT_SAMPLE: u64 = 0x0004000F;
+
+/// Maximum number of float samples Audacity stores in one block.
+pub const MAX_BLOCK_SAMPLES: usize = 262144;
+
+
+/// Min, max and RMS of a sample window.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Summary {
+    pub min: f32,
+    pub max: f32,
+    pub rms: f32,
+}
+
+
+impl Summary {
+    pub fn from_samples(samples: &[f32]) -> Self {
+        if samples.is_empty() {
+            return Self { min: 0f32, max: 0f32, rms: 0f32 };
+        }
+
+        let mut min = f32::MAX;
+        let mut max = f32::MIN;
+        let mut sumsq = 0f64;
+        for &val in samples {
+            min = min.min(val);
+            max = max.max(val);
+            sumsq += (val as f64) * (val as f64);
+        }
+        let rms = (sumsq / samples.len() as f64).sqrt() as f32;
+        Self { min, max, rms }
+    }
+}
+
+
+/// Content of one `sampleblocks` row.
+#[derive(Debug)]
+pub struct SampleBlock {
+    pub sampleformat: u64,
+    pub summary: Summary,
+    pub summary256: Vec<u8>,
+    pub summary64k: Vec<u8>,
+    pub samples: Vec<u8>,
+}
+
+
+impl SampleBlock {
+    pub fn from_samples(samples: &[f32]) -> Self {
+        let mut bytes = Vec::<u8>::with_capacity(samples.len() * 4);
+        for &val in samples {
+            bytes.write_f32::<LittleEndian>(val).unwrap();
+        }
+
+        Self {
+            sampleformat: FLOAT_SAMPLE,
+            summary: Summary::from_samples(samples),
+            summary256: encode_summaries(samples, 256),
+            summary64k: encode_summaries(samples, 65536),
+            samples: bytes,
+        }
+    }
+}
+
+
+/// Number of bytes one sample of `format` occupies on disk.
+///
+/// The upper 16 bits of an Audacity sample format code hold the sample
+/// size.
+pub fn sample_size(format: u64) -> usize {
+    (format >> 16) as usize
+}
+
+
+/// Decode the raw `samples` blob of a block stored in `format`.
+///
+/// Integer formats are scaled to [-1, 1). Returns `None` for unknown
+/// formats and for buffers that are not a whole number of samples.
+pub fn decode_samples(format: u64, bytes: &[u8]) -> Option<Vec<f32>> {
+    let size = sample_size(format);
+    if size == 0 || !bytes.len().is_multiple_of(size) {
+        return None;
+    }
+
+    let out = match format {
+        FLOAT_SAMPLE => bytes.chunks_exact(4).map(LittleEndian::read_f32).collect(),
+        INT16_SAMPLE => bytes.chunks_exact(2)
+            .map(|b| LittleEndian::read_i16(b) as f32 / 32768f32).collect(),
+        INT24_SAMPLE => bytes.chunks_exact(4)
+            .map(|b| LittleEndian::read_i32(b) as f32 / 8388608f32).collect(),
+        _ => return None
+    };
+    Some(out)
+}
+
+
+// Encode the (min, max, rms) triples of consecutive windows of `size`
+// samples the way Audacity stores them in `summary256` and `summary64k`.
+fn encode_summaries(samples: &[f32], size: usize) -> Vec<u8> {
+    let mut out = Vec::<u8>::with_capacity(samples.len().div_ceil(size) * 12);
+    for window in samples.chunks(size) {
+        let sm = Summary::from_samples(window);
+        out.write_f32::<LittleEndian>(sm.min).unwrap();
+        out.write_f32::<LittleEndian>(sm.max).unwrap();
+        out.write_f32::<LittleEndian>(sm.rms).unwrap();
+    }
+    out
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary_values() {
+        let sm = Summary::from_samples(&[-1f32, 1f32, -1f32, 1f32]);
+        assert_eq!(sm, Summary { min: -1f32, max: 1f32, rms: 1f32 });
+    }
+
+    #[test]
+    fn decode_formats() {
+        let block = SampleBlock::from_samples(&[0.5f32, -0.25f32]);
+        assert_eq!(decode_samples(FLOAT_SAMPLE, &block.samples), Some(vec![0.5f32, -0.25f32]));
+        assert_eq!(decode_samples(INT16_SAMPLE, &[0u8, 64u8]), Some(vec![0.5f32]));
+        assert_eq!(decode_samples(0x00040001, &[0u8, 0u8, 64u8, 0u8]), Some(vec![0.5f32]));
+        assert_eq!(decode_samples(FLOAT_SAMPLE, &[0u8; 3]), None);
+    }
+
+    #[test]
+    fn summary_sizes() {
+        let block = SampleBlock::from_samples(&vec![0f32; 300]);
+        assert_eq!(block.samples.len(), 1200);
+        assert_eq!(block.summary256.len(), 2 * 12);
+        assert_eq!(block.summary64k.len(), 12);
+    }
+}
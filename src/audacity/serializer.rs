@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+
+use crate::audacity::encoder::{encoded_len, Encoder};
+use crate::audacity::fields::FieldType;
+
+
+/// Char size used for names and strings. This is what Audacity writes on
+/// Linux and macOS, where `wxStringCharType` is UTF-32.
+pub const DEFAULT_CHAR_SIZE: u8 = 4;
+
+
+/// Binary XML writer.
+///
+/// Produces the `dict` and `doc` blobs of the `project` table in the same
+/// encoding `TagDict` and `ProjectDoc` decode. Element and attribute names
+/// are interned into the dict on first use.
+pub struct ProjectSerializer {
+    char_size: u8,
+    names: HashMap<String, i16>,
+    dict: Vec<u8>,
+    doc: Vec<u8>,
+}
+
+
+impl ProjectSerializer {
+    pub fn new() -> Self {
+        Self::with_char_size(DEFAULT_CHAR_SIZE)
+    }
+
+    pub fn with_char_size(char_size: u8) -> Self {
+        let mut dict = Vec::<u8>::new();
+        dict.field_type_code(0);
+        dict.byte(char_size);
+        Self {
+            char_size,
+            names: HashMap::new(),
+            dict,
+            doc: Vec::<u8>::new(),
+        }
+    }
+
+    pub fn start_tag(&mut self, name: &str) {
+        let id = self.name_id(name);
+        self.doc.field_type_code(1);
+        self.doc.short(id);
+    }
+
+    pub fn end_tag(&mut self, name: &str) {
+        let id = self.name_id(name);
+        self.doc.field_type_code(2);
+        self.doc.short(id);
+    }
+
+    pub fn write_str(&mut self, name: &str, value: &str) {
+        let id = self.name_id(name);
+        self.doc.field_type_code(3);
+        self.doc.short(id);
+        self.doc.integer(encoded_len(value, self.char_size) as i32);
+        self.doc.string(value, self.char_size);
+    }
+
+    pub fn write_int(&mut self, name: &str, value: i32) {
+        let id = self.name_id(name);
+        self.doc.field_type_code(4);
+        self.doc.short(id);
+        self.doc.integer(value);
+    }
+
+    pub fn write_bool(&mut self, name: &str, value: bool) {
+        let id = self.name_id(name);
+        self.doc.field_type_code(5);
+        self.doc.short(id);
+        self.doc.byte(value as u8);
+    }
+
+    pub fn write_longlong(&mut self, name: &str, value: i64) {
+        let id = self.name_id(name);
+        self.doc.field_type_code(7);
+        self.doc.short(id);
+        self.doc.longlong(value);
+    }
+
+    pub fn write_size_t(&mut self, name: &str, value: usize) {
+        let id = self.name_id(name);
+        self.doc.field_type_code(8);
+        self.doc.short(id);
+        self.doc.integer(value as i32);
+    }
+
+    pub fn write_double(&mut self, name: &str, value: f64) {
+        let id = self.name_id(name);
+        self.doc.field_type_code(10);
+        self.doc.short(id);
+        self.doc.double(value, -1);
+    }
+
+    /// Return the encoded `dict` and `doc` blobs.
+    pub fn finish(self) -> (Vec<u8>, Vec<u8>) {
+        (self.dict, self.doc)
+    }
+
+    fn name_id(&mut self, name: &str) -> i16 {
+        if let Some(id) = self.names.get(name) {
+            return *id;
+        }
+
+        let id = self.names.len() as i16;
+        self.names.insert(name.to_string(), id);
+        self.dict.field_type_code(15);
+        self.dict.short(id);
+        self.dict.short(encoded_len(name, self.char_size) as i16);
+        self.dict.string(name, self.char_size);
+        id
+    }
+}
+
+
+impl Default for ProjectSerializer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+/// Encode a decoded `field` back into its binary form.
+///
+/// Strings are encoded with char size `width`, which has to match the
+/// char size of the dict the field ids refer to.
+pub fn write_field(out: &mut Vec<u8>, field: &FieldType, width: u8) {
+    match field {
+        FieldType::CharSize { value } => {
+            out.field_type_code(0);
+            out.byte(*value);
+        },
+        FieldType::StartTag { id } => {
+            out.field_type_code(1);
+            out.short(*id);
+        },
+        FieldType::EndTag { id } => {
+            out.field_type_code(2);
+            out.short(*id);
+        },
+        FieldType::Str { id, value, .. } => {
+            out.field_type_code(3);
+            out.short(*id);
+            out.integer(encoded_len(value, width) as i32);
+            out.string(value, width);
+        },
+        FieldType::Int { id, value } => {
+            out.field_type_code(4);
+            out.short(*id);
+            out.integer(*value);
+        },
+        FieldType::Bool { id, value } => {
+            out.field_type_code(5);
+            out.short(*id);
+            out.byte(*value as u8);
+        },
+        FieldType::Long { id, value } => {
+            out.field_type_code(6);
+            out.short(*id);
+            out.integer(*value);
+        },
+        FieldType::LongLong { id, value } => {
+            out.field_type_code(7);
+            out.short(*id);
+            out.longlong(*value);
+        },
+        FieldType::SizeT { id, value } => {
+            out.field_type_code(8);
+            out.short(*id);
+            out.integer(*value as i32);
+        },
+        FieldType::Float { id, value, digits } => {
+            out.field_type_code(9);
+            out.short(*id);
+            out.float(*value, *digits);
+        },
+        FieldType::Double { id, value, digits } => {
+            out.field_type_code(10);
+            out.short(*id);
+            out.double(*value, *digits);
+        },
+        FieldType::Data { value, .. } => {
+            out.field_type_code(11);
+            out.integer(encoded_len(value, width) as i32);
+            out.string(value, width);
+        },
+        FieldType::Raw { value, .. } => {
+            out.field_type_code(12);
+            out.integer(encoded_len(value, width) as i32);
+            out.string(value, width);
+        },
+        FieldType::Push => out.field_type_code(13),
+        FieldType::Pop => out.field_type_code(14),
+        FieldType::Name { id, value, .. } => {
+            out.field_type_code(15);
+            out.short(*id);
+            out.short(encoded_len(value, width) as i16);
+            out.string(value, width);
+        },
+    }
+}
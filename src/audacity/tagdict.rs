@@ -1,11 +1,11 @@
 use std::collections::HashMap;
-use std::io::Seek;
+use std::io::{Error, Result, Seek};
 
 use rusqlite::{Connection, DatabaseName};
 use rusqlite::blob::Blob;
 
 use crate::audacity::fields::{CharSize, FieldType, ReadDictField};
-use crate::audacity::decoder::Decoder;
+use crate::audacity::decoder::{invalid, Decoder};
 
 
 #[derive(Debug)]
@@ -30,23 +30,23 @@ impl CharSize for TagDictReader {
 
 
 impl ReadDictField for TagDictReader {
-    fn read_field(&self, blob: &mut Blob) -> FieldType {
-        match blob.field_type_code() {
+    fn read_field(&self, blob: &mut Blob) -> Result<FieldType> {
+        match blob.field_type_code()? {
              0 => self.char_size(blob),
             15 => self.name(blob),
-             _ => panic!("Unknown field type code detected"),
+             code => Err(invalid(format!("Unknown field type code {}", code))),
         }
     }
 
-    fn char_size(&self, blob: &mut Blob) -> FieldType {
-        let f = blob.byte();
-        FieldType::CharSize { value: f }
+    fn char_size(&self, blob: &mut Blob) -> Result<FieldType> {
+        let f = blob.byte()?;
+        Ok(FieldType::CharSize { value: f })
     }
 
-    fn name(&self, blob: &mut Blob) -> FieldType {
-        let id = blob.short();
-        let size = blob.short();
-        FieldType::Name { id: id, size: size, value: blob.string(size as usize, self.chs()) }
+    fn name(&self, blob: &mut Blob) -> Result<FieldType> {
+        let id = blob.short()?;
+        let size = blob.short()?;
+        Ok(FieldType::Name { id: id, size: size, value: blob.string(size as usize, self.chs())? })
     }
 }
 
@@ -65,18 +65,35 @@ impl TagDict {
         }
     }
 
-    pub fn decode(&mut self, con: &Connection) {
+    pub fn decode(&mut self, con: &Connection) -> Result<()> {
+        self.decode_from(con, "project")
+    }
+
+    // Decode the dict of `table`, which is either "project" or "autosave".
+    pub fn decode_from(&mut self, con: &Connection, table: &str) -> Result<()> {
 
-        let mut blob = con.blob_open(DatabaseName::Main, "project",
-            "dict", 1, true).expect("Failed to read blob");
+        let mut blob = con.blob_open(DatabaseName::Main, table,
+            "dict", 1, true).map_err(Error::other)?;
 
-        while (blob.stream_position().expect("Cannot read") as usize) < blob.len() {
-            match self.read.read_field(&mut blob) {
+        while (blob.stream_position()? as usize) < blob.len() {
+            match self.read.read_field(&mut blob)? {
                 FieldType::CharSize { value } => {  self.read.char_size = value; },
                 FieldType::Name { id, value, .. } => {  self.dict.insert(id, value.clone()); },
-                _ => { panic!("asdfasdfasdf"); }
+                _ => { return Err(invalid("Unexpected field in dict")); }
             }
         }
+        Ok(())
+    }
+
+    // Decode as many names of `table` as possible. Returns `true` if the
+    // whole dict was read.
+    pub fn decode_partial(&mut self, con: &Connection, table: &str) -> bool {
+        self.decode_from(con, table).is_ok()
+    }
+
+    /// Name with `id`.
+    pub fn name(&self, id: i16) -> Result<&String> {
+        self.dict.get(&id).ok_or_else(|| invalid(format!("Name id {} not in dict", id)))
     }
 
     pub fn chs(&self) -> u8 {
@@ -93,6 +110,6 @@ mod tests {
     fn get_started() {
         let con = Connection::open("data/test-project.aup3").expect("open failed");
         let mut tags = TagDict::new();
-        tags.decode(&con);
+        tags.decode(&con).unwrap();
     }
 }
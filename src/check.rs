@@ -0,0 +1,251 @@
+//! Integrity checks for aup3 files.
+
+use std::collections::{HashMap, HashSet};
+
+use pyo3::prelude::*;
+use rusqlite::Connection;
+
+use crate::audacity::sampleblock::{decode_samples, sample_size, Summary};
+use crate::structure::WaveClip;
+
+
+/// Tolerance when comparing stored summaries to recomputed ones.
+const SUMMARY_TOLERANCE: f32 = 1e-5;
+
+
+#[derive(Debug, Clone)]
+#[pyclass]
+pub struct CheckIssue {
+    #[pyo3(get)]
+    pub kind: String,
+
+    #[pyo3(get)]
+    pub clip_index: Option<usize>,
+
+    #[pyo3(get)]
+    pub block_id: Option<i64>,
+
+    #[pyo3(get)]
+    pub message: String,
+}
+
+impl CheckIssue {
+    fn new(kind: &str, clip_index: Option<usize>, block_id: Option<i64>, message: String) -> Self {
+        Self { kind: kind.to_string(), clip_index, block_id, message }
+    }
+}
+
+#[pymethods]
+impl CheckIssue {
+    fn __str__(&self) -> String {
+        format!("CheckIssue(kind='{}', clip_index={:?}, block_id={:?}, message='{}')",
+            self.kind, self.clip_index, self.block_id, self.message)
+    }
+
+    fn __repr__(&self) -> String {
+        self.__str__()
+    }
+}
+
+
+#[derive(Debug, Clone)]
+#[pyclass]
+pub struct CheckReport {
+    #[pyo3(get)]
+    pub blocks_checked: usize,
+
+    #[pyo3(get)]
+    pub issues: Vec<CheckIssue>,
+}
+
+#[pymethods]
+impl CheckReport {
+    #[getter]
+    pub fn ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    fn __str__(&self) -> String {
+        format!("CheckReport(ok={}, blocks_checked={}, issues={})",
+            self.ok(), self.blocks_checked, self.issues.len())
+    }
+
+    fn __repr__(&self) -> String {
+        self.__str__()
+    }
+}
+
+
+// Metadata of one `sampleblocks` row.
+struct BlockRow {
+    sampleformat: u64,
+    summin: Option<f64>,
+    summax: Option<f64>,
+    size: usize,
+}
+
+
+/// Check the blocks referenced by `clips` against the `sampleblocks` table.
+///
+/// Verifies that every referenced block exists, that blob sizes match the
+/// sample format and block lengths, that sequences are contiguous and sum
+/// up to `numsamples`, that no rows are orphaned and that the stored
+/// summaries agree with the samples. Problems are collected, not raised.
+pub fn check(con: &Connection, clips: &[WaveClip]) -> CheckReport {
+    let mut issues = Vec::<CheckIssue>::new();
+
+    let rows = match read_block_rows(con) {
+        Ok(rows) => rows,
+        Err(err) => {
+            issues.push(CheckIssue::new("unreadable_table", None, None,
+                format!("Cannot read sampleblocks: {}", err)));
+            return CheckReport { blocks_checked: 0, issues };
+        }
+    };
+
+    let mut referenced = HashSet::<i64>::new();
+    for (ci, clip) in clips.iter().enumerate() {
+        let seq = match &clip.sequences {
+            Some(seq) => seq,
+            None => continue
+        };
+
+        let mut expected_start = 0usize;
+        let mut total = 0u64;
+        for (bi, block) in seq.blocks.iter().enumerate() {
+            let id = block.blockid as i64;
+            referenced.insert(id);
+
+            if block.start != expected_start {
+                issues.push(CheckIssue::new("gap", Some(ci), Some(id),
+                    format!("Block starts at {}, expected {}", block.start, expected_start)));
+            }
+
+            // length according to the document
+            let doc_len = match seq.blocks.get(bi + 1) {
+                Some(next) => next.start.saturating_sub(block.start),
+                None => (seq.numsamples as usize).saturating_sub(block.start),
+            };
+
+            let row = match rows.get(&id) {
+                Some(row) => row,
+                None => {
+                    issues.push(CheckIssue::new("missing_block", Some(ci), Some(id),
+                        "Block not found in sampleblocks".to_string()));
+                    expected_start = block.start + doc_len;
+                    total += doc_len as u64;
+                    continue;
+                }
+            };
+
+            let width = sample_size(row.sampleformat);
+            if width == 0 || row.size % width != 0 {
+                issues.push(CheckIssue::new("size_mismatch", Some(ci), Some(id),
+                    format!("Blob of {} bytes does not fit sample format {:#x}",
+                        row.size, row.sampleformat)));
+                expected_start = block.start + doc_len;
+                total += doc_len as u64;
+                continue;
+            }
+
+            let len = row.size / width;
+            if len != doc_len {
+                issues.push(CheckIssue::new("size_mismatch", Some(ci), Some(id),
+                    format!("Blob holds {} samples, document expects {}", len, doc_len)));
+            }
+            if row.sampleformat != seq.sampleformat {
+                issues.push(CheckIssue::new("format_mismatch", Some(ci), Some(id),
+                    format!("Block format {:#x} differs from sequence format {:#x}",
+                        row.sampleformat, seq.sampleformat)));
+            }
+
+            if let Some(issue) = check_summary(con, id, row, ci) {
+                issues.push(issue);
+            }
+
+            expected_start = block.start + len;
+            total += len as u64;
+        }
+
+        if total != seq.numsamples {
+            issues.push(CheckIssue::new("numsamples_mismatch", Some(ci), None,
+                format!("Blocks hold {} samples, sequence declares {}", total, seq.numsamples)));
+        }
+    }
+
+    let mut orphans: Vec<&i64> = rows.keys().filter(|id| !referenced.contains(id)).collect();
+    orphans.sort();
+    for id in orphans {
+        issues.push(CheckIssue::new("orphaned_block", None, Some(*id),
+            "Block is not referenced by the document".to_string()));
+    }
+
+    CheckReport { blocks_checked: referenced.len(), issues }
+}
+
+
+fn read_block_rows(con: &Connection) -> Result<HashMap<i64, BlockRow>, rusqlite::Error> {
+    let mut stmt = con.prepare(
+        "SELECT blockid, sampleformat, summin, summax, length(samples) FROM sampleblocks")?;
+    let rows = stmt.query_map([], |row| {
+        Ok((row.get::<_, i64>(0)?, BlockRow {
+            sampleformat: row.get::<_, i64>(1)? as u64,
+            summin: row.get(2)?,
+            summax: row.get(3)?,
+            size: row.get::<_, Option<i64>>(4)?.unwrap_or(0) as usize,
+        }))
+    })?;
+    rows.collect()
+}
+
+
+// Compare the stored `summin` and `summax` of block `id` to its samples.
+fn check_summary(con: &Connection, id: i64, row: &BlockRow, ci: usize) -> Option<CheckIssue> {
+    let bytes: Vec<u8> = match con.query_row(
+        "SELECT samples FROM sampleblocks WHERE blockid = ?1", [id], |r| r.get(0)) {
+        Ok(bytes) => bytes,
+        Err(err) => return Some(CheckIssue::new("unreadable_block", Some(ci), Some(id),
+            format!("Cannot read samples: {}", err)))
+    };
+
+    let samples = decode_samples(row.sampleformat, &bytes)?;
+    let actual = Summary::from_samples(&samples);
+    let stored = (row.summin.unwrap_or(0f64) as f32, row.summax.unwrap_or(0f64) as f32);
+    if (stored.0 - actual.min).abs() > SUMMARY_TOLERANCE
+        || (stored.1 - actual.max).abs() > SUMMARY_TOLERANCE {
+        return Some(CheckIssue::new("summary_mismatch", Some(ci), Some(id),
+            format!("Stored min/max ({}, {}) differ from samples ({}, {})",
+                stored.0, stored.1, actual.min, actual.max)));
+    }
+    None
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::project::Project;
+    use crate::writer::create;
+    use rusqlite::Connection;
+
+    #[test]
+    fn detect_problems() {
+        let path = std::env::temp_dir().join("aup3conv-check.aup3");
+        let _ = std::fs::remove_file(&path);
+        let path = path.to_str().unwrap();
+        create(path, 8000, &[vec![0.5f32; 300000]], &[]).unwrap();
+
+        let report = Project::open(path).unwrap().check().unwrap();
+        assert!(report.ok(), "{:?}", report.issues);
+        assert_eq!(report.blocks_checked, 2);
+
+        let con = Connection::open(path).unwrap();
+        con.execute("UPDATE sampleblocks SET summax = 0.9 WHERE blockid = 1", []).unwrap();
+        con.execute("INSERT INTO sampleblocks (sampleformat, samples) VALUES (262159, x'00000000')", []).unwrap();
+        con.execute("DELETE FROM sampleblocks WHERE blockid = 2", []).unwrap();
+        drop(con);
+
+        let report = Project::open(path).unwrap().check().unwrap();
+        let kinds: Vec<&str> = report.issues.iter().map(|x| x.kind.as_str()).collect();
+        assert_eq!(kinds, vec!["summary_mismatch", "missing_block", "orphaned_block"]);
+    }
+}
@@ -0,0 +1,251 @@
+//! Copy a project into a minimal, self-contained file.
+
+use std::collections::HashMap;
+use std::fs;
+
+use pyo3::prelude::*;
+use rusqlite::Connection;
+
+use crate::audacity::fields::FieldType;
+use crate::audacity::projectdoc::ProjectDoc;
+use crate::audacity::serializer::write_field;
+use crate::audacity::tagdict::TagDict;
+use crate::db::{self, OpenMode};
+use crate::writer::ProjectWriter;
+
+
+#[derive(Debug, Clone)]
+#[pyclass]
+pub struct CompactReport {
+    #[pyo3(get)]
+    pub blocks_copied: usize,
+
+    #[pyo3(get)]
+    pub blocks_dropped: usize,
+
+    #[pyo3(get)]
+    pub src_bytes: u64,
+
+    #[pyo3(get)]
+    pub dst_bytes: u64,
+
+    /// Whether `src` held unsaved changes in the autosave table, which
+    /// are not carried over.
+    #[pyo3(get)]
+    pub autosave_dropped: bool,
+}
+
+#[pymethods]
+impl CompactReport {
+    #[getter]
+    pub fn bytes_saved(&self) -> u64 {
+        self.src_bytes.saturating_sub(self.dst_bytes)
+    }
+
+    fn __str__(&self) -> String {
+        format!("CompactReport(blocks_copied={}, blocks_dropped={}, src_bytes={}, \
+            dst_bytes={}, bytes_saved={})", self.blocks_copied, self.blocks_dropped,
+            self.src_bytes, self.dst_bytes, self.bytes_saved())
+    }
+
+    fn __repr__(&self) -> String {
+        self.__str__()
+    }
+}
+
+
+/// Copy the saved document of `src` and the blocks it references to `dst`.
+///
+/// Block ids are renumbered in order of first reference and the document
+/// is re-encoded field by field with the new ids. Silent blocks (negative
+/// ids) are kept as they are. The result is vacuumed. On failure, the
+/// partly written `dst` is removed.
+pub fn compact(src: &str, dst: &str) -> Result<CompactReport, rusqlite::Error> {
+    let con = db::connect(src, OpenMode::ReadOnly)?;
+
+    let mut tagdict = TagDict::new();
+    tagdict.decode(&con).map_err(db::blob_error)?;
+    let blockid = tagdict.dict.iter()
+        .find(|(_, name)| name.as_str() == "blockid")
+        .map(|(id, _)| *id);
+
+    let dict: Vec<u8> = con.query_row("SELECT dict FROM project WHERE id = 1", [], |row| row.get(0))?;
+    let mut doc = ProjectDoc::new(tagdict);
+    let fields = doc.fields(&con, "project").map_err(db::blob_error)?;
+
+    let writer = ProjectWriter::create(dst)?;
+    let result = write_compact(&writer, &con, &dict, fields, blockid, doc.chs());
+    drop(writer);
+    let mapping = match result {
+        Ok(mapping) => mapping,
+        Err(err) => {
+            let _ = fs::remove_file(dst);
+            let _ = fs::remove_file(format!("{}-journal", dst));
+            return Err(err);
+        }
+    };
+
+    let total: i64 = con.query_row("SELECT count(*) FROM sampleblocks", [], |row| row.get(0))?;
+    Ok(CompactReport {
+        blocks_copied: mapping.len(),
+        blocks_dropped: (total as usize).saturating_sub(mapping.len()),
+        src_bytes: fs::metadata(src).map(|m| m.len()).unwrap_or(0),
+        dst_bytes: fs::metadata(dst).map(|m| m.len()).unwrap_or(0),
+        autosave_dropped: db::has_autosave(&con),
+    })
+}
+
+
+// Write `fields` with renumbered block ids and the blocks they reference
+// through `writer`. Returns the mapping from old to new block ids.
+fn write_compact(writer: &ProjectWriter, con: &Connection, dict: &[u8], fields: Vec<FieldType>,
+    blockid: Option<i16>, width: u8) -> Result<HashMap<i64, i64>, rusqlite::Error> {
+
+    let application_id: i32 = con.pragma_query_value(None, "application_id", |row| row.get(0))?;
+    let user_version: i32 = con.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    writer.set_version(application_id, user_version)?;
+    writer.begin()?;
+
+    let mut mapping = HashMap::<i64, i64>::new();
+    let mut width = width;
+    let mut out = Vec::<u8>::new();
+    for field in fields {
+        let field = match (blockid, &field) {
+            (Some(bid), _) if field_id(&field) == Some(bid) => {
+                renumber(&field, writer, con, &mut mapping)?
+            },
+            _ => field
+        };
+        if let FieldType::CharSize { value } = field {
+            width = value;
+        }
+        write_field(&mut out, &field, width);
+    }
+
+    writer.write_doc(dict, &out)?;
+    writer.commit()?;
+    writer.vacuum()?;
+    Ok(mapping)
+}
+
+
+fn field_id(field: &FieldType) -> Option<i16> {
+    match field {
+        FieldType::Int { id, .. } | FieldType::Long { id, .. }
+            | FieldType::LongLong { id, .. } | FieldType::SizeT { id, .. } => Some(*id),
+        _ => None
+    }
+}
+
+
+// Replace the block id held by `field`, copying the block on first use.
+fn renumber(field: &FieldType, writer: &ProjectWriter, src: &Connection,
+    mapping: &mut HashMap<i64, i64>) -> Result<FieldType, rusqlite::Error> {
+
+    let old = match field {
+        FieldType::Int { value, .. } | FieldType::Long { value, .. } => *value as i64,
+        FieldType::LongLong { value, .. } => *value,
+        FieldType::SizeT { value, .. } => *value as i64,
+        _ => return Ok(field.clone())
+    };
+    if old < 0 {
+        return Ok(field.clone());
+    }
+
+    let new = match mapping.get(&old) {
+        Some(new) => *new,
+        None => {
+            let new = writer.copy_block(src, old)?;
+            mapping.insert(old, new);
+            new
+        }
+    };
+
+    Ok(match field {
+        FieldType::Int { id, .. } => FieldType::Int { id: *id, value: new as i32 },
+        FieldType::Long { id, .. } => FieldType::Long { id: *id, value: new as i32 },
+        FieldType::SizeT { id, .. } => FieldType::SizeT { id: *id, value: new as usize },
+        FieldType::LongLong { id, .. } => FieldType::LongLong { id: *id, value: new },
+        _ => unreachable!()
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::Project;
+    use crate::audacity::audio::AudioLoader;
+    use crate::structure::Label;
+    use crate::writer::create;
+
+    #[test]
+    fn drop_unreferenced_blocks() {
+        let dir = std::env::temp_dir().join("aup3conv-compact");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let src = dir.join("src.aup3");
+        let dst = dir.join("dst.aup3");
+        let (src, dst) = (src.to_str().unwrap(), dst.to_str().unwrap());
+
+        let samples: Vec<f32> = (0..300000).map(|i| (i % 7) as f32).collect();
+        let labels = vec![Label::new(0.5, 1f64, "x".to_string())];
+        create(src, 8000, std::slice::from_ref(&samples), &labels).unwrap();
+
+        // Insert stale blocks in front, so that the ids of the referenced
+        // blocks have to change.
+        let con = Connection::open(src).unwrap();
+        con.execute_batch("
+            UPDATE sampleblocks SET blockid = blockid + 10;
+            INSERT INTO sampleblocks (blockid, sampleformat, samples)
+                VALUES (1, 262159, zeroblob(400000));").unwrap();
+        drop(con);
+        rewrite_block_ids(src, 10);
+
+        let report = compact(src, dst).unwrap();
+        assert_eq!(report.blocks_copied, 2);
+        assert_eq!(report.blocks_dropped, 1);
+        assert!(report.bytes_saved() > 0);
+
+        let project = Project::open(dst).unwrap();
+        assert_eq!(project.check().unwrap().issues.len(), 0);
+        assert_eq!(project.labels.as_ref().unwrap()[0].title, "x");
+        let mut out = Vec::<f32>::new();
+        project.load_slice(0f64, 1f64, &mut out).unwrap();
+        assert_eq!(out.as_slice(), &samples[..8000]);
+    }
+
+    #[test]
+    fn remove_dst_on_failure() {
+        let dir = std::env::temp_dir().join("aup3conv-compact-failure");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let src = dir.join("src.aup3");
+        let dst = dir.join("dst.aup3");
+        let (src, dst) = (src.to_str().unwrap(), dst.to_str().unwrap());
+        create(src, 8000, &[vec![0f32; 300000]], &[]).unwrap();
+        Connection::open(src).unwrap().execute("DELETE FROM sampleblocks WHERE blockid = 2", []).unwrap();
+
+        assert!(compact(src, dst).is_err());
+        assert!(!std::path::Path::new(dst).exists());
+    }
+
+    // Shift all block ids in the doc of `path` by `delta`.
+    fn rewrite_block_ids(path: &str, delta: i64) {
+        let con = Connection::open(path).unwrap();
+        let mut tagdict = TagDict::new();
+        tagdict.decode(&con).unwrap();
+        let bid = *tagdict.dict.iter().find(|(_, n)| n.as_str() == "blockid").unwrap().0;
+        let mut doc = ProjectDoc::new(tagdict);
+        let width = doc.chs();
+        let mut out = Vec::<u8>::new();
+        for field in doc.fields(&con, "project").unwrap() {
+            let field = match field {
+                FieldType::LongLong { id, value } if id == bid => FieldType::LongLong { id, value: value + delta },
+                other => other
+            };
+            write_field(&mut out, &field, width);
+        }
+        con.execute("UPDATE project SET doc = ?1 WHERE id = 1", [out]).unwrap();
+    }
+}
@@ -0,0 +1,192 @@
+//! Detect which Audacity release wrote a project and whether we can
+//! read all of it.
+
+use std::fmt;
+
+use pyo3::prelude::*;
+use rusqlite::Connection;
+
+use crate::structure::ProjectAttributes;
+use crate::writer::APPLICATION_ID;
+
+
+/// Newest project format version this crate has been checked against.
+pub const NEWEST_FORMAT: FormatVersion = FormatVersion { major: 3, minor: 4, revision: 0, mod_level: 0 };
+
+/// Element names of the project document this crate decodes. Other
+/// elements are skipped when decoding.
+pub const KNOWN_ELEMENTS: &[&str] = &[
+    "project", "tags", "tag", "wavetrack", "waveclip", "sequence", "waveblock",
+    "labeltrack", "label", "effects", "effect", "parameters", "parameter", "notetrack",
+    "timetrack",
+];
+
+/// Elements of volume envelopes. An envelope without control points
+/// leaves the audio unchanged, so `check` reports the control points of
+/// clip envelopes rather than the element names.
+const ENVELOPE_ELEMENTS: [&str; 2] = ["envelope", "controlpoint"];
+
+
+/// Project format version Audacity packs into SQLite's `user_version`,
+/// one byte per component.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FormatVersion {
+    pub major: u8,
+    pub minor: u8,
+    pub revision: u8,
+    pub mod_level: u8,
+}
+
+impl FormatVersion {
+    pub fn from_packed(value: i32) -> Self {
+        let [major, minor, revision, mod_level] = value.to_be_bytes();
+        Self { major, minor, revision, mod_level }
+    }
+}
+
+impl fmt::Display for FormatVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}.{}", self.major, self.minor, self.revision, self.mod_level)
+    }
+}
+
+
+/// Whether a project can be read completely.
+#[derive(Debug, Clone)]
+#[pyclass]
+pub struct Compatibility {
+    /// False if parts of the project may be missing or misread.
+    #[pyo3(get)]
+    pub supported: bool,
+
+    /// Project format version from `user_version`, e.g. "3.0.0.0".
+    #[pyo3(get)]
+    pub format_version: String,
+
+    /// SQLite `application_id`. Audacity writes 'AUDY'.
+    #[pyo3(get)]
+    pub application_id: i32,
+
+    /// Version of Audacity that saved the project, if recorded.
+    #[pyo3(get)]
+    pub audacity_version: Option<String>,
+
+    /// Elements of the document that are not decoded.
+    #[pyo3(get)]
+    pub unknown_elements: Vec<String>,
+
+    /// Reasons for `supported` being false.
+    #[pyo3(get)]
+    pub warnings: Vec<String>,
+}
+
+#[pymethods]
+impl Compatibility {
+    fn __str__(&self) -> String {
+        format!("Compatibility(supported={}, format_version='{}', audacity_version={:?}, warnings={})",
+            self.supported, self.format_version, self.audacity_version, self.warnings.len())
+    }
+
+    fn __repr__(&self) -> String {
+        self.__str__()
+    }
+}
+
+
+/// Check the file header of `con` and the decoded document.
+///
+/// `elements` are the element names found in the document and
+/// `clip_envelope_points` the number of control points in clip envelopes,
+/// which are not applied to the audio.
+pub fn check(con: &Connection, attributes: &ProjectAttributes, elements: &[String],
+    clip_envelope_points: usize) -> Result<Compatibility, rusqlite::Error> {
+    let application_id: i32 = con.pragma_query_value(None, "application_id", |row| row.get(0))?;
+    let user_version: i32 = con.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    let format = FormatVersion::from_packed(user_version);
+
+    let mut warnings = Vec::<String>::new();
+    if application_id != APPLICATION_ID {
+        warnings.push(format!("application_id {:#010x} is not Audacity's, the file may not be a project.",
+            application_id));
+    }
+    if format.major != NEWEST_FORMAT.major {
+        warnings.push(format!("Project format {} is not supported.", format));
+    } else if format > NEWEST_FORMAT {
+        warnings.push(format!("Project format {} is newer than {}, new features are not decoded.",
+            format, NEWEST_FORMAT));
+    }
+    if attributes.audacityversion.is_none() {
+        warnings.push("The project does not record the Audacity version that saved it.".to_string());
+    }
+
+    let mut unknown_elements: Vec<String> = elements.iter()
+        .filter(|name| !KNOWN_ELEMENTS.contains(&name.as_str()))
+        .filter(|name| !ENVELOPE_ELEMENTS.contains(&name.as_str()))
+        .cloned()
+        .collect();
+    unknown_elements.sort();
+    unknown_elements.dedup();
+    if !unknown_elements.is_empty() {
+        warnings.push(format!("Elements not decoded: {}.", unknown_elements.join(", ")));
+    }
+    if clip_envelope_points > 0 {
+        warnings.push(format!("Clip envelopes with {} control points are not applied.",
+            clip_envelope_points));
+    }
+
+    Ok(Compatibility {
+        supported: warnings.is_empty(),
+        format_version: format.to_string(),
+        application_id,
+        audacity_version: attributes.audacityversion.clone(),
+        unknown_elements,
+        warnings,
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::Project;
+    use crate::writer::{create, USER_VERSION};
+
+    #[test]
+    fn packed_version() {
+        let version = FormatVersion::from_packed(USER_VERSION);
+        assert_eq!(version.to_string(), "3.0.0.0");
+        assert_eq!((version.major, version.minor), (3, 0));
+        assert!(FormatVersion::from_packed(0x03010000) > version);
+    }
+
+    #[test]
+    fn detect_newer_format() {
+        let path = std::env::temp_dir().join("aup3conv-compat.aup3");
+        let _ = std::fs::remove_file(&path);
+        let path = path.to_str().unwrap();
+        create(path, 8000, &[vec![0f32; 100]], &[]).unwrap();
+
+        let compat = Project::open(path).unwrap().compatibility;
+        assert!(compat.supported, "{:?}", compat.warnings);
+        assert_eq!(compat.format_version, "3.0.0.0");
+        assert_eq!(compat.audacity_version.as_deref(), Some("3.0.0"));
+
+        Connection::open(path).unwrap().pragma_update(None, "user_version", 0x03090000).unwrap();
+        let compat = Project::open(path).unwrap().compatibility;
+        assert!(!compat.supported);
+        assert_eq!(compat.format_version, "3.9.0.0");
+        assert_eq!(compat.warnings.len(), 1);
+
+        let attributes = ProjectAttributes { audacityversion: Some("3.9.0".to_string()), ..Default::default() };
+        let elements = vec!["project".to_string(), "chord".to_string(), "chord".to_string()];
+        let con = Connection::open(path).unwrap();
+        let compat = check(&con, &attributes, &elements, 0).unwrap();
+        assert_eq!(compat.unknown_elements, vec!["chord".to_string()]);
+
+        let elements = vec!["project".to_string(), "envelope".to_string(), "controlpoint".to_string()];
+        let compat = check(&con, &attributes, &elements, 2).unwrap();
+        assert!(compat.unknown_elements.is_empty());
+        assert_eq!(compat.warnings.len(), 2);
+        assert!(compat.warnings[1].contains("2 control points"), "{:?}", compat.warnings);
+    }
+}
@@ -0,0 +1,347 @@
+//! SQLite access to aup3 files.
+
+use std::fmt;
+use std::io;
+use std::ops::Deref;
+use std::path::Path;
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use rusqlite::{Connection, OpenFlags, DatabaseName};
+use rusqlite::backup::{Backup, StepResult};
+use rusqlite::types::Type;
+
+
+/// How to connect to a project file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OpenMode {
+    /// Plain read-only connection. Sees committed changes in the WAL, but
+    /// may fail on locks held by a running Audacity.
+    ReadOnly,
+
+    /// Open with `immutable=1`. SQLite ignores locks and the WAL, so the
+    /// data reflects the last checkpoint only.
+    Immutable,
+
+    /// Copy the database, including the WAL, into memory within a single
+    /// read transaction and read from the copy.
+    Snapshot,
+
+    /// Read-write connection, for editing the project document. Do not
+    /// use on projects that are open in Audacity.
+    ReadWrite,
+}
+
+
+impl OpenMode {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "readonly" => Some(Self::ReadOnly),
+            "immutable" => Some(Self::Immutable),
+            "snapshot" => Some(Self::Snapshot),
+            "readwrite" => Some(Self::ReadWrite),
+            _ => None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::ReadOnly => "readonly",
+            Self::Immutable => "immutable",
+            Self::Snapshot => "snapshot",
+            Self::ReadWrite => "readwrite",
+        }
+    }
+}
+
+
+/// Which document of the project to decode.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DocSource {
+    /// The document of the last explicit save (`project` table).
+    Saved,
+
+    /// The unsaved state Audacity keeps in the `autosave` table.
+    Autosave,
+
+    /// `Autosave` if present, otherwise `Saved`. Audacity clears the
+    /// autosave row on save, so whenever it exists it is the newer one.
+    Latest,
+}
+
+
+impl DocSource {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "saved" => Some(Self::Saved),
+            "autosave" => Some(Self::Autosave),
+            "latest" => Some(Self::Latest),
+            _ => None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Saved => "saved",
+            Self::Autosave => "autosave",
+            Self::Latest => "latest",
+        }
+    }
+
+    /// Resolve `Latest` to the concrete source available in `con`.
+    pub fn resolve(&self, con: &Connection) -> Self {
+        match self {
+            Self::Latest => if has_autosave(con) { Self::Autosave } else { Self::Saved },
+            _ => *self
+        }
+    }
+
+    /// Name of the table holding the `dict` and `doc` blobs.
+    pub fn table(&self) -> &'static str {
+        match self {
+            Self::Autosave => "autosave",
+            _ => "project",
+        }
+    }
+}
+
+
+/// Time to wait for a lock held by another process.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+
+/// Connect to the project file at `path`.
+pub fn connect(path: &str, mode: OpenMode) -> Result<Connection, rusqlite::Error> {
+    match mode {
+        OpenMode::ReadOnly => {
+            let con = Connection::open_with_flags(path, read_only_flags())?;
+            con.busy_timeout(BUSY_TIMEOUT)?;
+            Ok(con)
+        },
+        OpenMode::Immutable => {
+            let uri = format!("file:{}?immutable=1", uri_escape(path));
+            Connection::open_with_flags(uri, read_only_flags() | OpenFlags::SQLITE_OPEN_URI)
+        },
+        OpenMode::Snapshot => {
+            let src = Connection::open_with_flags(path, read_only_flags())?;
+            src.busy_timeout(BUSY_TIMEOUT)?;
+            let mut dst = Connection::open_in_memory()?;
+            {
+                // Copying all pages in one step happens within a single read
+                // transaction, which makes the copy consistent.
+                let backup = Backup::new_with_names(&src, DatabaseName::Main,
+                    &mut dst, DatabaseName::Main)?;
+                let started = Instant::now();
+                loop {
+                    match backup.step(-1)? {
+                        StepResult::Done => break,
+                        _ if started.elapsed() > BUSY_TIMEOUT => {
+                            return Err(rusqlite::Error::SqliteFailure(
+                                rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_BUSY),
+                                Some("Timed out waiting for a read snapshot".to_string())));
+                        },
+                        _ => std::thread::sleep(Duration::from_millis(50)),
+                    }
+                }
+            }
+            Ok(dst)
+        },
+        OpenMode::ReadWrite => {
+            let con = Connection::open_with_flags(path,
+                OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_NO_MUTEX)?;
+            con.busy_timeout(BUSY_TIMEOUT)?;
+            Ok(con)
+        }
+    }
+}
+
+
+/// Maximum number of connections a `Pool` opens to one file.
+pub const MAX_CONNECTIONS: usize = 8;
+
+
+/// Read-only connections to one project file, shared between threads.
+///
+/// Connections are opened on demand up to `MAX_CONNECTIONS`. A snapshot
+/// lives in memory and cannot be reopened, so its pool holds exactly
+/// one connection and callers take turns.
+pub struct Pool {
+    path: String,
+    mode: OpenMode,
+    state: Mutex<PoolState>,
+    returned: Condvar,
+}
+
+impl fmt::Debug for Pool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Pool").field("path", &self.path).field("mode", &self.mode).finish()
+    }
+}
+
+struct PoolState {
+    idle: Vec<Connection>,
+    open: usize,
+}
+
+
+impl Pool {
+    /// Create a pool holding `con`, which was opened from `path` in `mode`.
+    pub fn new(path: &str, mode: OpenMode, con: Connection) -> Self {
+        Self {
+            path: path.to_string(),
+            mode,
+            state: Mutex::new(PoolState { idle: vec![con], open: 1 }),
+            returned: Condvar::new(),
+        }
+    }
+
+    /// Take a connection, waiting for one to be returned if the pool is
+    /// exhausted.
+    pub fn get(&self) -> Result<PooledConnection<'_>, rusqlite::Error> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(con) = state.idle.pop() {
+                return Ok(PooledConnection { pool: self, con: Some(con) });
+            }
+            if state.open < self.max_size() {
+                state.open += 1;
+                drop(state);
+                return match connect(&self.path, self.mode) {
+                    Ok(con) => Ok(PooledConnection { pool: self, con: Some(con) }),
+                    Err(err) => {
+                        self.state.lock().unwrap().open -= 1;
+                        self.returned.notify_one();
+                        Err(err)
+                    }
+                };
+            }
+            state = self.returned.wait(state).unwrap();
+        }
+    }
+
+    pub fn max_size(&self) -> usize {
+        match self.mode {
+            OpenMode::Snapshot => 1,
+            _ => MAX_CONNECTIONS,
+        }
+    }
+
+    fn put(&self, con: Connection) {
+        self.state.lock().unwrap().idle.push(con);
+        self.returned.notify_one();
+    }
+}
+
+
+/// Connection borrowed from a `Pool`. Returns to the pool on drop.
+pub struct PooledConnection<'a> {
+    pool: &'a Pool,
+    con: Option<Connection>,
+}
+
+impl Deref for PooledConnection<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.con.as_ref().unwrap()
+    }
+}
+
+impl Drop for PooledConnection<'_> {
+    fn drop(&mut self) {
+        if let Some(con) = self.con.take() {
+            self.pool.put(con);
+        }
+    }
+}
+
+
+/// Check whether another process currently has the project open.
+///
+/// Audacity keeps its projects in WAL mode. SQLite removes the `-wal`
+/// file when the last connection closes, so its presence indicates an
+/// active writer (or a crashed one).
+pub fn writer_active(path: &str) -> bool {
+    Path::new(&format!("{}-wal", path)).exists()
+}
+
+
+/// Check whether the `autosave` table holds unsaved changes.
+pub fn has_autosave(con: &Connection) -> bool {
+    con.query_row("SELECT count(*) FROM autosave", [], |row| row.get::<_, i64>(0))
+        .map(|count| count > 0)
+        .unwrap_or(false)
+}
+
+
+/// Wrap an error decoding the dict or doc blob of a project.
+pub fn blob_error(err: io::Error) -> rusqlite::Error {
+    rusqlite::Error::FromSqlConversionFailure(0, Type::Blob, Box::new(err))
+}
+
+
+fn read_only_flags() -> OpenFlags {
+    OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX
+}
+
+
+// Escape the characters that have special meaning in SQLite URIs.
+fn uri_escape(path: &str) -> String {
+    let mut out = String::with_capacity(path.len());
+    for ch in path.chars() {
+        match ch {
+            '%' => out.push_str("%25"),
+            '?' => out.push_str("%3f"),
+            '#' => out.push_str("%23"),
+            _ => out.push(ch),
+        }
+    }
+    out
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::writer::create;
+
+    #[test]
+    fn open_modes() {
+        let path = std::env::temp_dir().join("aup3conv-open-modes.aup3");
+        let _ = std::fs::remove_file(&path);
+        let path = path.to_str().unwrap();
+        create(path, 8000, &[vec![0f32; 100]], &[]).unwrap();
+
+        for mode in [OpenMode::ReadOnly, OpenMode::Immutable, OpenMode::Snapshot, OpenMode::ReadWrite] {
+            let con = connect(path, mode).unwrap();
+            assert!(!has_autosave(&con));
+        }
+        assert!(!writer_active(path));
+    }
+
+    #[test]
+    fn pool_connections() {
+        let path = std::env::temp_dir().join("aup3conv-pool.aup3");
+        let _ = std::fs::remove_file(&path);
+        let path = path.to_str().unwrap();
+        create(path, 8000, &[vec![0f32; 100]], &[]).unwrap();
+
+        let pool = Pool::new(path, OpenMode::ReadOnly, connect(path, OpenMode::ReadOnly).unwrap());
+        std::thread::scope(|scope| {
+            for _ in 0..2 * MAX_CONNECTIONS {
+                scope.spawn(|| {
+                    let con = pool.get().unwrap();
+                    assert!(!has_autosave(&con));
+                });
+            }
+        });
+        let state = pool.state.lock().unwrap();
+        assert!(state.open <= MAX_CONNECTIONS);
+        assert_eq!(state.idle.len(), state.open);
+    }
+
+    #[test]
+    fn escape() {
+        assert_eq!(uri_escape("/a b/c?d#e%f"), "/a b/c%3fd%23e%25f");
+    }
+}
@@ -0,0 +1,276 @@
+//! Write audio and labels to interchange formats.
+
+use std::fs::File;
+use std::io::{BufWriter, Result, Write};
+
+use byteorder::{LittleEndian, WriteBytesExt};
+
+use crate::structure::Label;
+
+
+/// Write mono 32 bit float samples to a WAV file.
+pub fn write_wav(path: &str, rate: u32, samples: &[f32]) -> Result<()> {
+    write_wav_with_info(path, rate, samples, &[])
+}
+
+
+/// Write mono 32 bit float samples and `metadata` to a WAV file.
+///
+/// Audacity's standard tags go to their RIFF INFO fields. INFO has no
+/// place for other keys, so they are appended to the comment as
+/// `key=value` lines.
+pub fn write_wav_with_info(path: &str, rate: u32, samples: &[f32], metadata: &[(String, String)])
+    -> Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    let data_size = (samples.len() * 4) as u32;
+    let info = info_chunk(metadata);
+
+    out.write_all(b"RIFF")?;
+    out.write_u32::<LittleEndian>(4 + 8 + 16 + 8 + data_size + info.len() as u32)?;
+    out.write_all(b"WAVE")?;
+
+    out.write_all(b"fmt ")?;
+    out.write_u32::<LittleEndian>(16)?;
+    out.write_u16::<LittleEndian>(3)?;          // IEEE float
+    out.write_u16::<LittleEndian>(1)?;          // channels
+    out.write_u32::<LittleEndian>(rate)?;
+    out.write_u32::<LittleEndian>(rate * 4)?;   // bytes per second
+    out.write_u16::<LittleEndian>(4)?;          // block align
+    out.write_u16::<LittleEndian>(32)?;         // bits per sample
+
+    out.write_all(b"data")?;
+    out.write_u32::<LittleEndian>(data_size)?;
+    for &val in samples {
+        out.write_f32::<LittleEndian>(val)?;
+    }
+    out.write_all(&info)?;
+    out.flush()
+}
+
+
+// RIFF INFO ids of Audacity's standard tags.
+const INFO_IDS: [(&str, &[u8; 4]); 9] = [
+    ("TITLE", b"INAM"), ("ARTIST", b"IART"), ("ALBUM", b"IPRD"), ("COMMENTS", b"ICMT"),
+    ("YEAR", b"ICRD"), ("GENRE", b"IGNR"), ("TRACKNUMBER", b"ITRK"), ("COPYRIGHT", b"ICOP"),
+    ("SOFTWARE", b"ISFT"),
+];
+
+
+// Encode `metadata` as a LIST/INFO chunk. Empty if there is nothing to write.
+fn info_chunk(metadata: &[(String, String)]) -> Vec<u8> {
+    let mut fields = Vec::<(&[u8; 4], String)>::new();
+    let mut extra = Vec::<String>::new();
+    for (key, value) in metadata {
+        match INFO_IDS.iter().find(|(name, _)| key.eq_ignore_ascii_case(name)) {
+            Some((_, id)) => fields.push((id, value.clone())),
+            None => extra.push(format!("{}={}", key, value)),
+        }
+    }
+    if !extra.is_empty() {
+        match fields.iter_mut().find(|(id, _)| *id == b"ICMT") {
+            Some((_, comment)) => {
+                comment.push('\n');
+                comment.push_str(&extra.join("\n"));
+            },
+            None => fields.push((b"ICMT", extra.join("\n"))),
+        }
+    }
+    if fields.is_empty() {
+        return Vec::new();
+    }
+
+    let mut body = Vec::<u8>::new();
+    body.extend_from_slice(b"INFO");
+    for (id, value) in fields {
+        // zero terminated, padded to an even size
+        let mut text = value.into_bytes();
+        text.push(0);
+        body.extend_from_slice(id);
+        body.extend_from_slice(&(text.len() as u32).to_le_bytes());
+        if text.len() % 2 == 1 {
+            text.push(0);
+        }
+        body.extend_from_slice(&text);
+    }
+
+    let mut chunk = Vec::<u8>::with_capacity(body.len() + 8);
+    chunk.extend_from_slice(b"LIST");
+    chunk.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    chunk.extend_from_slice(&body);
+    chunk
+}
+
+
+/// Samples per FLAC frame.
+const FLAC_BLOCK_SIZE: usize = 4096;
+
+
+/// Write mono samples as 24 bit FLAC with `metadata` as Vorbis comments.
+///
+/// Frames are stored verbatim, without prediction, so the file is about
+/// as large as uncompressed 24 bit audio. Samples are clipped to [-1, 1].
+/// Keys are upper-cased, Audacity's "Comments" and "Year" become COMMENT
+/// and DATE.
+pub fn write_flac(path: &str, rate: u32, samples: &[f32], metadata: &[(String, String)]) -> Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    out.write_all(b"fLaC")?;
+
+    // STREAMINFO
+    let mut info = Vec::<u8>::with_capacity(34);
+    info.extend_from_slice(&(FLAC_BLOCK_SIZE as u16).to_be_bytes());
+    info.extend_from_slice(&(FLAC_BLOCK_SIZE as u16).to_be_bytes());
+    info.extend_from_slice(&[0u8; 6]);          // frame sizes unknown
+    let packed: u64 = ((rate as u64) << 44) | (23u64 << 36) | samples.len() as u64;
+    info.extend_from_slice(&packed.to_be_bytes());
+    info.extend_from_slice(&[0u8; 16]);         // MD5 not computed
+    write_flac_block(&mut out, 0, false, &info)?;
+
+    let vendor = concat!("aup3conv ", env!("CARGO_PKG_VERSION"));
+    let mut comments = Vec::<u8>::new();
+    comments.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+    comments.extend_from_slice(vendor.as_bytes());
+    comments.extend_from_slice(&(metadata.len() as u32).to_le_bytes());
+    for (key, value) in metadata {
+        let entry = format!("{}={}", vorbis_key(key), value);
+        comments.extend_from_slice(&(entry.len() as u32).to_le_bytes());
+        comments.extend_from_slice(entry.as_bytes());
+    }
+    write_flac_block(&mut out, 4, true, &comments)?;
+
+    for (k, block) in samples.chunks(FLAC_BLOCK_SIZE).enumerate() {
+        let mut frame = vec![0xFF, 0xF8, 0x70, 0x0C];  // fixed block size, 24 bit mono
+        utf8_number(&mut frame, k as u64);
+        frame.extend_from_slice(&((block.len() - 1) as u16).to_be_bytes());
+        frame.push(crc8(&frame));
+
+        frame.push(0x02);                               // verbatim subframe
+        for &val in block {
+            let val = (val.clamp(-1f32, 1f32) as f64 * 8388607f64).round() as i32;
+            frame.extend_from_slice(&val.to_be_bytes()[1..]);
+        }
+        let crc = crc16(&frame);
+        frame.extend_from_slice(&crc.to_be_bytes());
+        out.write_all(&frame)?;
+    }
+    out.flush()
+}
+
+
+fn write_flac_block(out: &mut impl Write, kind: u8, last: bool, body: &[u8]) -> Result<()> {
+    out.write_all(&[kind | if last { 0x80 } else { 0 }])?;
+    out.write_all(&(body.len() as u32).to_be_bytes()[1..])?;
+    out.write_all(body)
+}
+
+
+fn vorbis_key(key: &str) -> String {
+    match key.to_ascii_uppercase().as_str() {
+        "COMMENTS" => "COMMENT".to_string(),
+        "YEAR" => "DATE".to_string(),
+        other => other.replace('=', "_"),
+    }
+}
+
+
+// Append `value` in FLAC's UTF-8 like variable length coding.
+fn utf8_number(out: &mut Vec<u8>, value: u64) {
+    if value < 0x80 {
+        out.push(value as u8);
+        return;
+    }
+    let mut n = 2;
+    while value >= 1u64 << (5 * n + 1) {
+        n += 1;
+    }
+    let lead = (0xFF00u16 >> n) as u8;
+    out.push(lead | (value >> (6 * (n - 1))) as u8);
+    for i in (0..n - 1).rev() {
+        out.push(0x80 | ((value >> (6 * i)) & 0x3F) as u8);
+    }
+}
+
+
+fn crc8(data: &[u8]) -> u8 {
+    let mut crc = 0u8;
+    for &byte in data {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 { (crc << 1) ^ 0x07 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0u16;
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x8005 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+
+/// Write labels in Audacity's label text format.
+///
+/// Each label is one line of tab separated start, stop and title. Labels
+/// with a frequency range are followed by a line starting with `\` that
+/// holds the low and high frequency, -1 standing for undefined.
+pub fn write_label_file(path: &str, labels: &[Label]) -> Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    for label in labels {
+        writeln!(out, "{:.6}\t{:.6}\t{}", label.t, label.t1, label.title)?;
+        if label.has_band() {
+            writeln!(out, "\\\t{:.6}\t{:.6}", label.f0.unwrap_or(-1f64), label.f1.unwrap_or(-1f64))?;
+        }
+    }
+    out.flush()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata() -> Vec<(String, String)> {
+        vec![("Title".to_string(), "session 4".to_string()),
+             ("SubjectID".to_string(), "S017".to_string())]
+    }
+
+    #[test]
+    fn wav_info() {
+        let path = std::env::temp_dir().join("aup3conv-info.wav");
+        let path = path.to_str().unwrap();
+        write_wav_with_info(path, 8000, &[0f32; 10], &metadata()).unwrap();
+        let bytes = std::fs::read(path).unwrap();
+        let riff_size = u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+        assert_eq!(riff_size + 8, bytes.len());
+        let list = &bytes[44 + 40..];
+        assert_eq!(&list[..4], b"LIST");
+        assert_eq!(&list[8..16], b"INFOINAM");
+        let text = String::from_utf8_lossy(list);
+        assert!(text.contains("session 4") && text.contains("ICMT") && text.contains("SubjectID=S017"));
+    }
+
+    #[test]
+    fn flac_roundtrip() {
+        let path = std::env::temp_dir().join("aup3conv-export.flac");
+        let path = path.to_str().unwrap();
+        let samples: Vec<f32> = (0..10000).map(|i| ((i % 200) as f32 - 100f32) / 100f32).collect();
+        write_flac(path, 22050, &samples, &metadata()).unwrap();
+
+        let mut reader = claxon::FlacReader::open(path).unwrap();
+        assert_eq!(reader.streaminfo().sample_rate, 22050);
+        assert_eq!(reader.streaminfo().samples, Some(10000));
+        assert_eq!(reader.get_tag("TITLE").next(), Some("session 4"));
+        assert_eq!(reader.get_tag("SUBJECTID").next(), Some("S017"));
+        let decoded: Vec<i32> = reader.samples().map(|x| x.unwrap()).collect();
+        assert_eq!(decoded.len(), 10000);
+        for (x, y) in decoded.iter().zip(&samples) {
+            assert!((*x as f32 / 8388607f32 - y).abs() < 1e-6);
+        }
+    }
+}
@@ -0,0 +1,110 @@
+//! Frequency filters for audio excerpts.
+
+use std::f64::consts::PI;
+
+
+/// Q factors of the two second order sections of a fourth order
+/// Butterworth filter.
+const BUTTERWORTH_Q: [f64; 2] = [0.541_196_100_146_197, 1.306_562_964_876_376_7];
+
+
+#[derive(Debug, Clone, Copy)]
+struct Biquad {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+}
+
+
+impl Biquad {
+    fn lowpass(fc: f64, fps: f64, q: f64) -> Self {
+        let w0 = 2f64 * PI * fc / fps;
+        let alpha = w0.sin() / (2f64 * q);
+        let cos = w0.cos();
+        Self::normalized((1f64 - cos) / 2f64, 1f64 - cos, (1f64 - cos) / 2f64,
+            1f64 + alpha, -2f64 * cos, 1f64 - alpha)
+    }
+
+    fn highpass(fc: f64, fps: f64, q: f64) -> Self {
+        let w0 = 2f64 * PI * fc / fps;
+        let alpha = w0.sin() / (2f64 * q);
+        let cos = w0.cos();
+        Self::normalized((1f64 + cos) / 2f64, -(1f64 + cos), (1f64 + cos) / 2f64,
+            1f64 + alpha, -2f64 * cos, 1f64 - alpha)
+    }
+
+    fn normalized(b0: f64, b1: f64, b2: f64, a0: f64, a1: f64, a2: f64) -> Self {
+        Self { b0: b0 / a0, b1: b1 / a0, b2: b2 / a0, a1: a1 / a0, a2: a2 / a0 }
+    }
+
+    // Filter `x` in place (transposed direct form II).
+    fn process<'a>(&self, x: impl Iterator<Item = &'a mut f32>) {
+        let (mut z1, mut z2) = (0f64, 0f64);
+        for val in x {
+            let inp = *val as f64;
+            let out = self.b0 * inp + z1;
+            z1 = self.b1 * inp - self.a1 * out + z2;
+            z2 = self.b2 * inp - self.a2 * out;
+            *val = out as f32;
+        }
+    }
+}
+
+
+/// Band-pass filter `samples` to the band from `low` to `high` Hz.
+///
+/// Applies fourth order Butterworth high- and low-pass sections forward
+/// and backward, so the result has no phase shift. A missing edge, a
+/// `low` of zero or a `high` at or above Nyquist skips that side.
+pub fn bandpass(samples: &[f32], fps: u32, low: Option<f64>, high: Option<f64>) -> Vec<f32> {
+    let fps = fps as f64;
+    let mut sections = Vec::<Biquad>::new();
+    if let Some(low) = low.filter(|f| *f > 0f64 && *f < fps / 2f64) {
+        sections.extend(BUTTERWORTH_Q.iter().map(|q| Biquad::highpass(low, fps, *q)));
+    }
+    if let Some(high) = high.filter(|f| *f > 0f64 && *f < fps / 2f64) {
+        sections.extend(BUTTERWORTH_Q.iter().map(|q| Biquad::lowpass(high, fps, *q)));
+    }
+
+    let mut out = samples.to_vec();
+    for section in &sections {
+        section.process(out.iter_mut());
+        section.process(out.iter_mut().rev());
+    }
+    out
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(freq: f64, fps: u32, n: usize) -> Vec<f32> {
+        (0..n).map(|i| (2f64 * PI * freq * i as f64 / fps as f64).sin() as f32).collect()
+    }
+
+    fn rms(x: &[f32]) -> f32 {
+        // skip the edges, where the filter settles
+        let x = &x[x.len() / 4..3 * x.len() / 4];
+        (x.iter().map(|v| v * v).sum::<f32>() / x.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn pass_and_stop() {
+        let fps = 16000;
+        let inband = bandpass(&sine(1000f64, fps, 8000), fps, Some(500f64), Some(2000f64));
+        let below = bandpass(&sine(100f64, fps, 8000), fps, Some(500f64), Some(2000f64));
+        let above = bandpass(&sine(6000f64, fps, 8000), fps, Some(500f64), Some(2000f64));
+        assert!((rms(&inband) - std::f32::consts::FRAC_1_SQRT_2).abs() < 0.01);
+        assert!(rms(&below) < 0.001);
+        assert!(rms(&above) < 0.001);
+    }
+
+    #[test]
+    fn no_band() {
+        let x = sine(440f64, 8000, 100);
+        assert_eq!(bandpass(&x, 8000, None, Some(4000f64)), x);
+    }
+}
@@ -1,21 +1,101 @@
 use pyo3::prelude::*;
-use pyo3::exceptions::PyIOError;
+use pyo3::exceptions::{PyIOError, PyUserWarning, PyValueError};
 
 mod tagstack;
 mod structure;
 mod io;
+mod db;
+mod check;
+mod compat;
+mod export;
+mod salvage;
+mod compact;
+mod filter;
+mod query;
+mod analysis;
+mod table;
+mod scan;
+mod resample;
+mod stretch;
+mod midi;
+mod timewarp;
+mod metadata;
 pub mod utils;
 pub mod audacity;
 pub mod project;
+pub mod writer;
 
 use project::Project;
+use db::{DocSource, OpenMode};
+use structure::{Label, LabelTrack, NoteTrack, ProjectAttributes, RealtimeEffect, TimeTrack, WaveTrack};
 
 
 #[pyfunction]
-fn open(path: String) -> PyResult<Project> {
-    match Project::open(&path) {
-        Ok(project) => Ok(project),
-        Err(_) => Err(PyIOError::new_err(format!("File not fould '{}'.", &path)))
+#[pyo3(signature = (path, mode="readonly", source="saved"))]
+fn open(py: Python<'_>, path: String, mode: &str, source: &str) -> PyResult<Project> {
+    let mode = match OpenMode::parse(mode) {
+        Some(mode) => mode,
+        None => return Err(PyValueError::new_err(format!("Unknown open mode '{}'.", mode)))
+    };
+    let source = match DocSource::parse(source) {
+        Some(source) => source,
+        None => return Err(PyValueError::new_err(format!("Unknown document source '{}'.", source)))
+    };
+    match py.allow_threads(|| Project::open_with(&path, mode, source)) {
+        Ok(project) => {
+            // Tell the user when results may be incomplete.
+            for warning in project.compatibility.warnings.iter() {
+                PyErr::warn_bound(py, &py.get_type_bound::<PyUserWarning>(),
+                    &format!("{}: {}", &path, warning), 1)?;
+            }
+            Ok(project)
+        },
+        Err(err) => Err(PyIOError::new_err(format!("Could not open '{}': {}", &path, err)))
+    }
+}
+
+
+#[pyfunction]
+#[pyo3(signature = (path, rate, tracks, labels=None, track_rates=None))]
+fn create(path: String, rate: u32, tracks: Vec<Vec<f32>>, labels: Option<Vec<Label>>,
+    track_rates: Option<Vec<u32>>) -> PyResult<()> {
+    let track_rates = track_rates.unwrap_or_else(|| vec![rate; tracks.len()]);
+    if track_rates.len() != tracks.len() {
+        return Err(PyValueError::new_err("Need one rate per track."));
+    }
+    match writer::create_with_rates(&path, rate, &tracks, &track_rates, &labels.unwrap_or_default()) {
+        Ok(()) => Ok(()),
+        Err(err) => Err(PyIOError::new_err(format!("Could not create '{}': {}", &path, err)))
+    }
+}
+
+
+#[pyfunction(name = "salvage")]
+fn salvage_project(path: String, out_dir: String) -> PyResult<salvage::SalvageReport> {
+    match salvage::salvage(&path, &out_dir) {
+        Ok(report) => Ok(report),
+        Err(err) => Err(PyIOError::new_err(format!("Could not salvage '{}': {}", &path, err)))
+    }
+}
+
+
+#[pyfunction(name = "compact")]
+fn compact_project(src: String, dst: String) -> PyResult<compact::CompactReport> {
+    match compact::compact(&src, &dst) {
+        Ok(report) => Ok(report),
+        Err(err) => Err(PyIOError::new_err(format!("Could not compact '{}': {}", &src, err)))
+    }
+}
+
+
+#[pyfunction(name = "scan")]
+#[pyo3(signature = (root, pattern="**/*.aup3", workers=None))]
+fn scan_projects(root: String, pattern: &str, workers: Option<usize>) -> PyResult<scan::Scan> {
+    let workers = workers.unwrap_or_else(||
+        std::thread::available_parallelism().map_or(1, |n| n.get()));
+    match scan::scan(&root, pattern, workers) {
+        Ok(results) => Ok(scan::Scan::new(results)),
+        Err(err) => Err(PyValueError::new_err(format!("Bad pattern '{}': {}", pattern, err)))
     }
 }
 
@@ -23,5 +103,25 @@ fn open(path: String) -> PyResult<Project> {
 #[pymodule]
 fn _aup3conv(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(open, m)?)?;
+    m.add_function(wrap_pyfunction!(create, m)?)?;
+    m.add_function(wrap_pyfunction!(salvage_project, m)?)?;
+    m.add_function(wrap_pyfunction!(compact_project, m)?)?;
+    m.add_function(wrap_pyfunction!(scan_projects, m)?)?;
+    m.add_class::<Label>()?;
+    m.add_class::<LabelTrack>()?;
+    m.add_class::<ProjectAttributes>()?;
+    m.add_class::<WaveTrack>()?;
+    m.add_class::<RealtimeEffect>()?;
+    m.add_class::<NoteTrack>()?;
+    m.add_class::<TimeTrack>()?;
+    m.add_class::<compat::Compatibility>()?;
+    m.add_class::<check::CheckReport>()?;
+    m.add_class::<check::CheckIssue>()?;
+    m.add_class::<salvage::SalvageReport>()?;
+    m.add_class::<compact::CompactReport>()?;
+    m.add_class::<analysis::LabelReport>()?;
+    m.add_class::<analysis::LabelFinding>()?;
+    m.add_class::<scan::Scan>()?;
+    m.add_class::<scan::ScanResult>()?;
     Ok(())
 }
@@ -0,0 +1,117 @@
+//! Edit the metadata tags of a project in place.
+
+use rusqlite::Connection;
+
+use crate::audacity::fields::FieldType;
+use crate::audacity::projectdoc::ProjectDoc;
+use crate::audacity::serializer::write_field;
+use crate::audacity::tagdict::TagDict;
+use crate::db;
+
+
+/// Replace the `tags` element in the document of `table` with `metadata`.
+///
+/// The document is re-encoded field by field, so everything but the tags
+/// stays as it is. Without a `tags` element, the new one is inserted
+/// after the attributes of `project`, where Audacity writes it. Names
+/// missing from the dict are appended to it.
+pub fn write_metadata(con: &Connection, table: &str, metadata: &[(String, String)])
+    -> Result<(), rusqlite::Error> {
+    let mut tagdict = TagDict::new();
+    tagdict.decode_from(con, table).map_err(db::blob_error)?;
+    let mut dict: Vec<u8> = con.query_row(
+        &format!("SELECT dict FROM {} WHERE id = 1", table), [], |row| row.get(0))?;
+
+    let mut next_id = tagdict.dict.keys().max().map_or(0, |id| id + 1);
+    let mut name_id = |name: &str, dict: &mut Vec<u8>| -> i16 {
+        if let Some((id, _)) = tagdict.dict.iter().find(|(_, value)| value.as_str() == name) {
+            return *id;
+        }
+        let id = next_id;
+        next_id += 1;
+        write_field(dict, &FieldType::Name { id, size: 0, value: name.to_string() }, tagdict.chs());
+        tagdict.dict.insert(id, name.to_string());
+        id
+    };
+    let project = name_id("project", &mut dict);
+    let tags = name_id("tags", &mut dict);
+    let tag = name_id("tag", &mut dict);
+    let name = name_id("name", &mut dict);
+    let value = name_id("value", &mut dict);
+
+    let mut doc_dict = TagDict::new();
+    doc_dict.decode_from(con, table).map_err(db::blob_error)?;
+    let mut fields = ProjectDoc::new(doc_dict).fields(con, table).map_err(db::blob_error)?;
+
+    let start = fields.iter().position(|field| matches!(field, FieldType::StartTag { id } if *id == tags));
+    let at = match start {
+        Some(start) => {
+            let stop = fields[start..].iter()
+                .position(|field| matches!(field, FieldType::EndTag { id } if *id == tags))
+                .map_or(fields.len(), |n| start + n + 1);
+            fields.drain(start..stop);
+            start
+        },
+        None => {
+            let open = fields.iter()
+                .position(|field| matches!(field, FieldType::StartTag { id } if *id == project))
+                .ok_or(rusqlite::Error::QueryReturnedNoRows)?;
+            fields[open + 1..].iter()
+                .position(|field| matches!(field, FieldType::StartTag { .. } | FieldType::EndTag { .. }))
+                .map_or(fields.len(), |n| open + 1 + n)
+        }
+    };
+
+    let mut new = vec![FieldType::StartTag { id: tags }];
+    for (key, val) in metadata {
+        new.push(FieldType::StartTag { id: tag });
+        new.push(FieldType::Str { id: name, size: 0, value: key.clone() });
+        new.push(FieldType::Str { id: value, size: 0, value: val.clone() });
+        new.push(FieldType::EndTag { id: tag });
+    }
+    new.push(FieldType::EndTag { id: tags });
+    fields.splice(at..at, new);
+
+    let mut width = tagdict.chs();
+    let mut out = Vec::<u8>::new();
+    for field in fields {
+        if let FieldType::CharSize { value } = field {
+            width = value;
+        }
+        write_field(&mut out, &field, width);
+    }
+
+    con.execute(&format!("UPDATE {} SET dict = ?1, doc = ?2 WHERE id = 1", table), (&dict, &out))?;
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{connect, OpenMode};
+    use crate::project::Project;
+    use crate::structure::Label;
+    use crate::writer::create;
+
+    #[test]
+    fn replace_tags() {
+        let path = std::env::temp_dir().join("aup3conv-metadata.aup3");
+        let _ = std::fs::remove_file(&path);
+        let path = path.to_str().unwrap();
+        create(path, 8000, &[vec![0f32; 100]], &[Label::new(0f64, 0.01, "a".to_string())]).unwrap();
+        assert!(Project::open(path).unwrap().metadata.is_empty());
+
+        let first = vec![("Title".to_string(), "x".to_string()), ("SubjectID".to_string(), "S1".to_string())];
+        let second = vec![("Artist".to_string(), "lab".to_string())];
+        let con = connect(path, OpenMode::ReadWrite).unwrap();
+        write_metadata(&con, "project", &first).unwrap();
+        assert_eq!(Project::open(path).unwrap().metadata, first);
+
+        write_metadata(&con, "project", &second).unwrap();
+        let project = Project::open(path).unwrap();
+        assert_eq!(project.metadata, second);
+        assert_eq!(project.labels.unwrap()[0].title, "a");
+        assert_eq!(project.num_tracks, 1);
+    }
+}
@@ -0,0 +1,345 @@
+//! Convert Allegro note data to Standard MIDI Files.
+//!
+//! Audacity serializes note tracks in Allegro's text format: one event
+//! per line, with attributes such as `T` (time in seconds), `V` (channel),
+//! `K` (key), `P` (pitch), `U` (duration in seconds), `L` (loudness) and
+//! `-name:value` parameters for controller updates.
+
+use std::fs::File;
+use std::io::{BufWriter, Result, Write};
+
+
+/// Ticks per quarter note of the files written.
+const DIVISION: u16 = 480;
+
+/// Tempo of the files written, in microseconds per quarter note (120 BPM).
+/// With `DIVISION` this gives 960 ticks per second.
+const TEMPO: u32 = 500_000;
+
+const TICKS_PER_SECOND: f64 = DIVISION as f64 * 1e6 / TEMPO as f64;
+
+
+/// Channel message at a time in seconds.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MidiEvent {
+    Note { time: f64, channel: u8, key: u8, velocity: u8, duration: f64 },
+    Control { time: f64, channel: u8, number: u8, value: u8 },
+    Program { time: f64, channel: u8, program: u8 },
+    Bend { time: f64, channel: u8, value: u16 },
+    Pressure { time: f64, channel: u8, key: Option<u8>, value: u8 },
+}
+
+impl MidiEvent {
+    pub fn time(&self) -> f64 {
+        match self {
+            Self::Note { time, .. } | Self::Control { time, .. } | Self::Program { time, .. }
+                | Self::Bend { time, .. } | Self::Pressure { time, .. } => *time
+        }
+    }
+
+    fn shift(&mut self, offset: f64) {
+        match self {
+            Self::Note { time, .. } | Self::Control { time, .. } | Self::Program { time, .. }
+                | Self::Bend { time, .. } | Self::Pressure { time, .. } => *time += offset
+        }
+    }
+}
+
+
+/// Events of one Allegro track.
+#[derive(Debug, Clone, Default)]
+pub struct AllegroTrack {
+    pub name: Option<String>,
+    pub events: Vec<MidiEvent>,
+}
+
+
+/// Parse Allegro text into tracks.
+///
+/// Times are in seconds from the start of the sequence, shifted by a
+/// `#offset` line if present. Attributes missing from a line keep their
+/// value from the previous line, as in Allegro. Events given in beats
+/// and parameters without a MIDI equivalent are skipped.
+pub fn parse_allegro(text: &str) -> Vec<AllegroTrack> {
+    let mut tracks = vec![AllegroTrack::default()];
+    let mut offset = 0f64;
+    let (mut time, mut channel, mut key, mut pitch, mut duration, mut loud) =
+        (0f64, 0u8, None::<i32>, None::<f64>, 0f64, 100f64);
+
+    for line in text.lines() {
+        let line = line.trim();
+        if let Some(rest) = line.strip_prefix("#track") {
+            let mut parts = rest.trim().splitn(2, char::is_whitespace);
+            let _index = parts.next();
+            let name = parts.next().map(|x| x.trim().to_string()).filter(|x| !x.is_empty());
+            if tracks.last().is_some_and(|track| !track.events.is_empty() || track.name.is_some()) {
+                tracks.push(AllegroTrack::default());
+            }
+            tracks.last_mut().unwrap().name = name;
+            continue;
+        }
+        if let Some(rest) = line.strip_prefix("#offset") {
+            offset = rest.trim().parse::<f64>().unwrap_or(0f64);
+            continue;
+        }
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut is_note = false;
+        let mut beats = false;
+        let mut key_given = false;
+        let mut updates = Vec::<(String, String)>::new();
+        for token in tokenize(line) {
+            if let Some(param) = token.strip_prefix('-') {
+                if let Some((name, value)) = param.split_once(':') {
+                    updates.push((name.to_string(), value.trim_matches('"').to_string()));
+                }
+                continue;
+            }
+            let (attr, value) = token.split_at(token.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(token.len()));
+            match attr {
+                "T" => if let Ok(val) = value.parse() { time = val },
+                "TW" | "TB" => beats = true,
+                "V" => channel = value.parse::<i32>().map_or(0, |val| val.clamp(0, 15) as u8),
+                "K" => { key = value.parse().ok(); key_given = true; },
+                "P" => { pitch = value.parse().ok(); is_note = true },
+                "U" => { if let Ok(val) = value.parse() { duration = val }; is_note = true },
+                "L" => if let Ok(val) = value.parse() { loud = val },
+                _ => {}
+            }
+        }
+        if beats {
+            continue;
+        }
+
+        let events = &mut tracks.last_mut().unwrap().events;
+        let time = time + offset;
+        if is_note {
+            let number = pitch.map(|p| p.round() as i32).or(key).unwrap_or(60).clamp(0, 127) as u8;
+            let velocity = loud.round().clamp(1f64, 127f64) as u8;
+            events.push(MidiEvent::Note { time, channel, key: number, velocity, duration });
+        }
+        let update_key = if key_given { key.map(|k| k.clamp(0, 127) as u8) } else { None };
+        for (name, value) in updates {
+            if let Some(event) = update_event(time, channel, update_key, &name, &value) {
+                events.push(event);
+            }
+        }
+    }
+    tracks.retain(|track| !track.events.is_empty() || track.name.is_some());
+    tracks
+}
+
+
+// Split an event line at whitespace, keeping quoted parameter values whole.
+fn tokenize(line: &str) -> Vec<String> {
+    let mut out = Vec::<String>::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for ch in line.chars() {
+        match ch {
+            '"' => { quoted = !quoted; current.push(ch) },
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    out.push(std::mem::take(&mut current));
+                }
+            },
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        out.push(current);
+    }
+    out
+}
+
+
+// Map an Allegro update to a channel message. The last letter of the
+// parameter name gives the type: `r` real, `i` integer.
+fn update_event(time: f64, channel: u8, key: Option<u8>, name: &str, value: &str) -> Option<MidiEvent> {
+    let real = || value.parse::<f64>().ok();
+    let unit = |x: f64| (x * 127f64).round().clamp(0f64, 127f64) as u8;
+    match name {
+        "programi" => Some(MidiEvent::Program { time, channel,
+            program: value.parse::<i32>().ok()?.clamp(0, 127) as u8 }),
+        "bendr" => Some(MidiEvent::Bend { time, channel,
+            value: ((real()? + 1f64) * 8192f64).round().clamp(0f64, 16383f64) as u16 }),
+        "pressurer" => Some(MidiEvent::Pressure { time, channel, key, value: unit(real()?) }),
+        _ => {
+            let number = name.strip_prefix("control")?.strip_suffix('r')?.parse::<u8>().ok()?;
+            (number < 128).then(|| MidiEvent::Control { time, channel, number, value: unit(real().unwrap_or(0f64)) })
+        }
+    }
+}
+
+
+/// Write `tracks` to a format 1 Standard MIDI File.
+///
+/// Event times are shifted by `offset` seconds. Notes that end before
+/// zero are dropped, notes and updates before zero start at zero. The
+/// file has a fixed tempo of 120 BPM, so ticks map linearly to seconds.
+pub fn write_smf(path: &str, tracks: &[AllegroTrack], offset: f64) -> Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    out.write_all(b"MThd")?;
+    out.write_all(&6u32.to_be_bytes())?;
+    out.write_all(&1u16.to_be_bytes())?;
+    out.write_all(&(tracks.len() as u16 + 1).to_be_bytes())?;
+    out.write_all(&DIVISION.to_be_bytes())?;
+
+    let mut conductor = vec![0x00, 0xFF, 0x51, 0x03];
+    conductor.extend_from_slice(&TEMPO.to_be_bytes()[1..]);
+    write_chunk(&mut out, conductor)?;
+
+    for track in tracks {
+        // (tick, order, message): note offs sort before other messages
+        // at the same tick, so repeated notes are not cut short.
+        let mut messages = Vec::<(u64, u8, Vec<u8>)>::new();
+        for event in track.events.iter() {
+            let mut event = event.clone();
+            event.shift(offset);
+            let tick = to_ticks(event.time());
+            match event {
+                MidiEvent::Note { time, channel, key, velocity, duration } => {
+                    let end = to_ticks(time + duration);
+                    if time < 0f64 && time + duration <= 0f64 {
+                        continue;
+                    }
+                    messages.push((tick, 1, vec![0x90 | channel, key, velocity]));
+                    messages.push((end.max(tick), 0, vec![0x80 | channel, key, 0]));
+                },
+                MidiEvent::Control { channel, number, value, .. } =>
+                    messages.push((tick, 1, vec![0xB0 | channel, number, value])),
+                MidiEvent::Program { channel, program, .. } =>
+                    messages.push((tick, 1, vec![0xC0 | channel, program])),
+                MidiEvent::Bend { channel, value, .. } =>
+                    messages.push((tick, 1, vec![0xE0 | channel, (value & 0x7F) as u8, (value >> 7) as u8])),
+                MidiEvent::Pressure { channel, key: Some(key), value, .. } =>
+                    messages.push((tick, 1, vec![0xA0 | channel, key, value])),
+                MidiEvent::Pressure { channel, key: None, value, .. } =>
+                    messages.push((tick, 1, vec![0xD0 | channel, value])),
+            }
+        }
+        messages.sort_by_key(|(tick, order, _)| (*tick, *order));
+
+        let mut chunk = Vec::<u8>::new();
+        if let Some(name) = &track.name {
+            chunk.push(0x00);
+            chunk.extend_from_slice(&[0xFF, 0x03]);
+            write_vlq(&mut chunk, name.len() as u64);
+            chunk.extend_from_slice(name.as_bytes());
+        }
+        let mut last = 0u64;
+        for (tick, _, message) in messages {
+            write_vlq(&mut chunk, tick - last);
+            chunk.extend_from_slice(&message);
+            last = tick;
+        }
+        write_chunk(&mut out, chunk)?;
+    }
+    out.flush()
+}
+
+
+fn to_ticks(time: f64) -> u64 {
+    (time.max(0f64) * TICKS_PER_SECOND).round() as u64
+}
+
+
+// Write an MTrk chunk holding `events`, terminated by end of track.
+fn write_chunk(out: &mut impl Write, mut events: Vec<u8>) -> Result<()> {
+    events.extend_from_slice(&[0x00, 0xFF, 0x2F, 0x00]);
+    out.write_all(b"MTrk")?;
+    out.write_all(&(events.len() as u32).to_be_bytes())?;
+    out.write_all(&events)
+}
+
+
+// Append `value` as a MIDI variable length quantity.
+fn write_vlq(out: &mut Vec<u8>, value: u64) {
+    let mut bytes = vec![(value & 0x7F) as u8];
+    let mut rest = value >> 7;
+    while rest > 0 {
+        bytes.push(0x80 | (rest & 0x7F) as u8);
+        rest >>= 7;
+    }
+    bytes.reverse();
+    out.extend_from_slice(&bytes);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEQ: &str = "#track 0\n\
+        T0 -tempor:120\n\
+        #track 1 Piano\n\
+        T0.5 V1 K60 P60 U0.25 L90\n\
+        T1.0 V1 -control7r:0.5\n\
+        T1.0 V1 K62 P62.2 U0.5 L100 -name:\"a b\"\n";
+
+    // Read the track chunks back as (delta, bytes) lists.
+    fn read_tracks(bytes: &[u8]) -> Vec<Vec<(u64, Vec<u8>)>> {
+        let mut out = Vec::new();
+        let mut pos = 14;
+        while pos < bytes.len() {
+            assert_eq!(&bytes[pos..pos + 4], b"MTrk");
+            let len = u32::from_be_bytes(bytes[pos + 4..pos + 8].try_into().unwrap()) as usize;
+            let data = &bytes[pos + 8..pos + 8 + len];
+            let mut events = Vec::new();
+            let mut i = 0;
+            while i < data.len() {
+                let mut delta = 0u64;
+                loop {
+                    delta = (delta << 7) | (data[i] & 0x7F) as u64;
+                    i += 1;
+                    if data[i - 1] & 0x80 == 0 { break }
+                }
+                let size = match data[i] {
+                    0xFF => 3 + data[i + 2] as usize,
+                    0xC0..=0xDF => 2,
+                    _ => 3,
+                };
+                events.push((delta, data[i..i + size].to_vec()));
+                i += size;
+            }
+            out.push(events);
+            pos += 8 + len;
+        }
+        out
+    }
+
+    #[test]
+    fn parse_sequence() {
+        let tracks = parse_allegro(SEQ);
+        assert_eq!(tracks.len(), 1);
+        assert_eq!(tracks[0].name.as_deref(), Some("Piano"));
+        assert_eq!(tracks[0].events, vec![
+            MidiEvent::Note { time: 0.5, channel: 1, key: 60, velocity: 90, duration: 0.25 },
+            MidiEvent::Control { time: 1.0, channel: 1, number: 7, value: 64 },
+            MidiEvent::Note { time: 1.0, channel: 1, key: 62, velocity: 100, duration: 0.5 },
+        ]);
+    }
+
+    #[test]
+    fn write_with_offset() {
+        let path = std::env::temp_dir().join("aup3conv-notes.mid");
+        let path = path.to_str().unwrap();
+        write_smf(path, &parse_allegro(SEQ), 2f64).unwrap();
+        let bytes = std::fs::read(path).unwrap();
+        assert_eq!(&bytes[..4], b"MThd");
+        assert_eq!(u16::from_be_bytes([bytes[10], bytes[11]]), 2);
+
+        let tracks = read_tracks(&bytes);
+        assert_eq!(tracks[0][0], (0, vec![0xFF, 0x51, 0x03, 0x07, 0xA1, 0x20]));
+        let events = &tracks[1];
+        assert_eq!(events[0].1[..2], [0xFF, 0x03]);
+        // 2.5 s at 960 ticks per second
+        assert_eq!(events[1], (2400, vec![0x91, 60, 90]));
+        assert_eq!(events[2], (240, vec![0x81, 60, 0]));
+        assert_eq!(events[3], (240, vec![0xB1, 7, 64]));
+        assert_eq!(events[4], (0, vec![0x91, 62, 100]));
+        assert_eq!(events[5], (480, vec![0x81, 62, 0]));
+    }
+}
@@ -1,17 +1,37 @@
 use std::io::{Read,Seek,SeekFrom};
-use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::panic;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::thread;
 
+use byteorder::{ByteOrder, LittleEndian};
 use rusqlite;
-use rusqlite::{DatabaseName,Connection,OpenFlags};
+use rusqlite::DatabaseName;
 use pyo3::prelude::*;
-use pyo3::exceptions::PyIOError;
+use pyo3::exceptions::{PyIOError, PyValueError};
+use pyo3::types::PyDict;
+use regex::Regex;
 
+use crate::analysis::{self, clip_extents, AnalysisOptions, LabelReport};
+use crate::check::{self, CheckReport};
+use crate::compat::{self, Compatibility};
+use crate::db::{self, DocSource, OpenMode};
+use crate::audacity::decoder::invalid;
 use crate::audacity::projectdoc::ProjectDoc;
 use crate::audacity::tagdict::TagDict;
 use crate::io::*;
 use crate::structure::*;
 use crate::audacity::audio::{AudioLoader, AudioProcessor, AudioError};
 use crate::utils::*;
+use crate::export;
+use crate::filter;
+use crate::metadata;
+use crate::query::{LabelKind, LabelQuery};
+use crate::resample::resample;
+use crate::stretch::{pitch_shift, time_stretch};
+use crate::table::{self, Table};
+use crate::timewarp;
 
 
 #[pyclass]
@@ -21,11 +41,14 @@ pub struct Project {
     path: String,
 
     #[pyo3(get)]
-    fps: u32,
+    pub fps: u32,
 
     #[pyo3(get)]
     pub labels: Option<Vec<Label>>,
 
+    #[pyo3(get)]
+    pub label_tracks: Option<Vec<LabelTrack>>,
+
     #[pyo3(get)]
     waveblocks: Option<Vec<WaveBlock>>,
 
@@ -33,45 +56,117 @@ pub struct Project {
     sequences: Option<Vec<Sequence>>,
 
     #[pyo3(get)]
-    waveclips: Option<Vec<WaveClip>>,
+    pub waveclips: Option<Vec<WaveClip>>,
+
+    /// Number of wave tracks, counting each channel of a stereo track.
+    #[pyo3(get)]
+    pub num_tracks: usize,
+
+    /// Wave tracks, one per channel.
+    #[pyo3(get)]
+    pub tracks: Vec<WaveTrack>,
+
+    /// Time track, if the project has one.
+    #[pyo3(get)]
+    pub time_track: Option<TimeTrack>,
+
+    /// Allegro note tracks.
+    #[pyo3(get)]
+    pub note_tracks: Vec<NoteTrack>,
+
+    /// Realtime effects of the master channel.
+    #[pyo3(get)]
+    pub master_effects: Vec<RealtimeEffect>,
+
+    /// Attributes of the `project` element.
+    #[pyo3(get)]
+    pub attributes: ProjectAttributes,
+
+    /// Format version and what could not be decoded.
+    #[pyo3(get)]
+    pub compatibility: Compatibility,
+
+    /// Name and value of the project's metadata tags.
+    pub metadata: Vec<(String, String)>,
+
+    mode: OpenMode,
 
-    con: Connection
+    #[pyo3(get)]
+    writer_active: bool,
+
+    #[pyo3(get)]
+    unsaved_changes: bool,
+
+    source: DocSource,
+
+    pool: Arc<db::Pool>
 }
 
 
 impl Project {
     pub fn open(path: &str) -> Result<Self, rusqlite::Error> {
-        let con = Connection::open_with_flags(
-            path,
-            OpenFlags::SQLITE_OPEN_READ_ONLY
-            | OpenFlags::SQLITE_OPEN_NO_MUTEX)?;
+        Self::open_with(path, OpenMode::ReadOnly, DocSource::Saved)
+    }
+
+    pub fn open_with(path: &str, mode: OpenMode, source: DocSource) -> Result<Self, rusqlite::Error> {
+        let writer_active = db::writer_active(path);
+        let con = db::connect(path, mode)?;
+        let unsaved_changes = db::has_autosave(&con);
+
+        let source = source.resolve(&con);
+        if source == DocSource::Autosave && !unsaved_changes {
+            return Err(rusqlite::Error::SqliteFailure(
+                rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_NOTFOUND),
+                Some("project has no autosave document".to_string())));
+        }
 
         let mut tagdict = TagDict::new();
-        tagdict.decode(&con);
+        tagdict.decode_from(&con, source.table()).map_err(db::blob_error)?;
 
         let mut doc = ProjectDoc::new(tagdict);
-        match doc.decode(&con) {
-            Ok(()) => {
-
-                let fps = match doc.parse_sample_rate() {
-                    Some(val) => val,
-                    None => panic!("Parsing failed")
-                };
-
-                Ok(Self {
-                    path: path.to_string(),
-                    fps: fps,
-                    labels: doc.parse_labels().unwrap(),
-                    waveblocks: doc.parse_waveblocks().unwrap(),
-                    sequences: doc.parse_sequences().unwrap(),
-                    waveclips: doc.parse_waveclips().unwrap(),
-                    con: con })
-            },
-            Err(err) => panic!("Error decoding project document: {}", err)
+        doc.decode_from(&con, source.table()).map_err(db::blob_error)?;
+
+        let fps = doc.parse_sample_rate()
+            .ok_or_else(|| db::blob_error(invalid("Project has no sample rate")))?;
+
+        let attributes = doc.parse_project_attributes().map_err(db::blob_error)?.unwrap_or_default();
+        let compatibility = compat::check(&con, &attributes, &doc.element_names(),
+            doc.clip_envelope_points())?;
+
+        let pool = Arc::new(db::Pool::new(path, mode, con));
+        let mut waveclips = doc.parse_waveclips().map_err(db::blob_error)?;
+        for clip in waveclips.iter_mut().flatten() {
+            clip.rate.get_or_insert(fps);
+            clip.pool = Some(Arc::clone(&pool));
         }
+
+        Ok(Self {
+            path: path.to_string(),
+            fps: fps,
+            labels: doc.parse_labels().map_err(db::blob_error)?,
+            label_tracks: doc.parse_label_tracks().map_err(db::blob_error)?,
+            waveblocks: doc.parse_waveblocks().map_err(db::blob_error)?,
+            sequences: doc.parse_sequences().map_err(db::blob_error)?,
+            waveclips,
+            num_tracks: doc.parse_num_wavetracks(),
+            tracks: doc.parse_wavetracks().map_err(db::blob_error)?,
+            time_track: doc.parse_time_track().map_err(db::blob_error)?,
+            note_tracks: doc.parse_note_tracks().map_err(db::blob_error)?,
+            master_effects: doc.parse_master_effects().map_err(db::blob_error)?,
+            attributes,
+            compatibility,
+            metadata: doc.parse_metadata(),
+            mode,
+            writer_active,
+            unsaved_changes,
+            source,
+            pool })
     }
 
-    fn clip_idx_from_time(&self, pos: f64) -> usize {
+    // Index of the last clip whose audible part starts at or, with `end`,
+    // before `pos`. A slice ending where the next clip starts thus stays
+    // in the clip before.
+    fn clip_idx_from_time(&self, pos: f64, end: bool) -> usize {
         if pos < 0f64 {
             panic!("POS {} is less than zero", pos);
         }
@@ -80,7 +175,7 @@ impl Project {
         if let Some(clips) = &self.waveclips {
             for (i, clip) in clips.iter().enumerate().rev() {
                 if clip.is_empty() { continue }
-                if pos >= clip.offset {
+                if pos > clip.play_start() || (!end && pos == clip.play_start()) {
                     index = i;
                     break;
                 }
@@ -90,30 +185,67 @@ impl Project {
     }
 
 
-    // Convert a time to a Position.
-    //
-    // The positive floating point value `time` is converted to the
-    // exact position in the data structure, packed into an Position object.
-    fn pos_from_time(&self, pos: f64) -> Position {
-        if pos < 0f64 {
-            panic!("POS {} is less than zero", pos);
+    // Index of the clip holding the project frame `frame`.
+    fn clip_idx_from_frame(&self, frame: u64) -> usize {
+        let mut index: usize = 0;
+        if let Some(clips) = &self.waveclips {
+            for (i, clip) in clips.iter().enumerate().rev() {
+                if clip.is_empty() { continue }
+                if frame >= self.clip_start_frame(clip) + clip.play_frames(self.fps).0 {
+                    index = i;
+                    break;
+                }
+            }
         }
+        index
+    }
+
+    /// Apply stretch ratio and pitch shift of the clip at time `start` to
+    /// raw `samples` read from it.
+    pub fn render_stretch(&self, start: f64, samples: &[f32]) -> Vec<f32> {
+        let idx = self.clip_idx_from_time(start.max(0f64), false);
+        let clip = match self.waveclips.as_ref().and_then(|clips| clips.get(idx)) {
+            Some(clip) if clip.is_stretched() => clip,
+            _ => return samples.to_vec()
+        };
+        let rate = clip.rate_or(self.fps);
+        let out = time_stretch(samples, clip.stretch_ratio, rate);
+        pitch_shift(&out, clip.cent_shift as f64, rate)
+    }
+
+    /// Sample rate of the clip at time `pos`.
+    pub fn rate_at(&self, pos: f64) -> u32 {
+        let idx = self.clip_idx_from_time(pos.max(0f64), false);
+        self.waveclips.as_ref()
+            .and_then(|clips| clips.get(idx))
+            .map_or(self.fps, |clip| clip.rate_or(self.fps))
+    }
+
+    // Project frame of the first sample of `clip`.
+    fn clip_start_frame(&self, clip: &WaveClip) -> u64 {
+        time_to_frame(clip.offset, self.fps)
+    }
 
+    // Convert the frame `fpos` relative to the start of clip `clip_idx`
+    // to a Position.
+    fn pos_in_clip(&self, clip_idx: usize, fpos: u64, end: bool) -> Position {
         let mut block_index: usize = 0;
         let mut block_id: u16 = 0;
         let mut byte_pos: usize = 0;
         let mut offtrack: bool = true;
-        let clip_idx = self.clip_idx_from_time(pos);
         if let Some(clips) = &self.waveclips {
             if let Some(seq) = &clips[clip_idx].sequences {
-                // position in frames relative to the clip
-                let fpos = time_to_frame(pos-clips[clip_idx].offset, self.fps);
                 for (i, block) in seq.blocks.iter().enumerate().rev() {
-                    if fpos >= block.start as u64 {
+                    let inside = if end && i > 0 {
+                        fpos > block.start as u64
+                    } else {
+                        fpos >= block.start as u64
+                    };
+                    if inside {
                         block_index = i;
                         block_id = block.blockid;
                         byte_pos = (fpos as usize - block.start) * 4;
-                        offtrack = if fpos > seq.numsamples {true} else {false};
+                        offtrack = fpos > seq.numsamples;
                         break;
                     }
                 }
@@ -124,14 +256,16 @@ impl Project {
 
     }
 
-    // get the block sequence to be read
-    // returns vector of (block_id, start, stop)
-    // where start and stop is in bytes!!!
-    fn block_range(&self, start: f64, stop: f64) -> Vec<ReadPosition> {
-        let mut out = Vec::<ReadPosition>::new();
+    // Blocks to read from `start` frame of clip `clip_idx` on for `n`
+    // frames. Integer frames map to byte offsets exactly.
+    fn frame_range(&self, clip_idx: usize, start: u64, n: u64) -> Result<Vec<ReadPosition>, AudioError> {
+        let start_pos = self.pos_in_clip(clip_idx, start, false);
+        let stop_pos = self.pos_in_clip(clip_idx, start + n, true);
+        self.positions_between(&start_pos, &stop_pos)
+    }
 
-        let start_pos = self.pos_from_time(start);
-        let stop_pos = self.pos_from_time(stop);
+    fn positions_between(&self, start_pos: &Position, stop_pos: &Position) -> Result<Vec<ReadPosition>, AudioError> {
+        let mut out = Vec::<ReadPosition>::new();
         if start_pos.clip_index == stop_pos.clip_index {
             if start_pos.block_index == stop_pos.block_index {
                 let rp = ReadPosition::new(start_pos.block_id, start_pos.offset, Some(stop_pos.offset));
@@ -148,8 +282,10 @@ impl Project {
                     let rp_0 = ReadPosition::new(start_pos.block_id, start_pos.offset, None);
                     out.push(rp_0);
 
-                    for _ in start_pos.block_index+1..stop_pos.block_index {
-                        let rpx = ReadPosition::new(start_pos.block_id, 0, None);
+                    let blocks = &self.waveclips.as_ref().unwrap()[start_pos.clip_index]
+                        .sequences.as_ref().unwrap().blocks;
+                    for block in &blocks[start_pos.block_index+1..stop_pos.block_index] {
+                        let rpx = ReadPosition::new(block.blockid, 0, None);
                         out.push(rpx);
                     }
                     let rp_n = ReadPosition::new(stop_pos.block_id, 0, Some(stop_pos.offset));
@@ -159,9 +295,176 @@ impl Project {
 
         // desired slice stretches over multiple clips
         } else {
-            panic!("Slice streches over multiple clips. Not implemented!");
+            return Err(AudioError::MultipleClips);
         }
-        out
+        Ok(out)
+    }
+
+    /// Build the "labels", "clips" or "blocks" table.
+    pub fn table(&self, name: &str) -> PyResult<Table> {
+        let clips = self.waveclips.as_deref().unwrap_or(&[]);
+        match name {
+            "labels" => Ok(table::labels_table(self.labels.as_deref().unwrap_or(&[]))),
+            "clips" => Ok(table::clips_table(clips, self.fps)),
+            "blocks" => Ok(table::blocks_table(clips)),
+            other => Err(PyValueError::new_err(format!("Unknown table '{}'.", other)))
+        }
+    }
+
+    // Load the samples from `start` to `stop`, spreading the blocks of
+    // the slice over up to `workers` threads with a connection each.
+    //
+    // Times map to the raw samples of the clip through its offset and
+    // stretch. Parts of the slice outside the audible part of the clip,
+    // such as trimmed audio, are silent.
+    fn read_slice(&self, start: f64, stop: f64, workers: usize, out: &mut Vec<f32>) -> Result<(), AudioError> {
+        let clips = self.waveclips.as_deref().unwrap_or(&[]);
+        let mut idx = self.clip_idx_from_time(start.max(0f64), false);
+        let last = if stop > start { self.clip_idx_from_time(stop.max(0f64), true) } else { idx };
+        if last != idx {
+            // from the silence after a clip into the next one
+            if clips[idx].play_end() > start {
+                return Err(AudioError::MultipleClips);
+            }
+            idx = last;
+        }
+        let clip = clips.get(idx).ok_or(AudioError::NoWaveblocks)?;
+
+        // frames from the first sample of the clip, negative before it
+        let rate = clip.rate_or(self.fps);
+        let frame = |t: f64| {
+            let rel = (t - clip.offset) / clip.stretch_ratio;
+            if rel < 0f64 { -(time_to_frame(-rel, rate) as i64) } else { time_to_frame(rel, rate) as i64 }
+        };
+        let (first, last) = (frame(start), frame(stop).max(frame(start)));
+        let (play_start, play_stop) = clip.play_frames(self.fps);
+        let (lo, hi) = (first.max(play_start as i64), last.min(play_stop as i64));
+
+        out.clear();
+        if lo >= hi {
+            out.resize((last - first) as usize, 0f32);
+            return Ok(());
+        }
+        let mut samples = Vec::<f32>::new();
+        self.read_items(&self.frame_range(idx, lo as u64, (hi - lo) as u64)?, workers, &mut samples)?;
+        out.resize((lo - first) as usize, 0f32);
+        out.append(&mut samples);
+        out.resize(out.len() + (last - hi) as usize, 0f32);
+        Ok(())
+    }
+
+    fn read_items(&self, items: &[ReadPosition], workers: usize, out: &mut Vec<f32>) -> Result<(), AudioError> {
+        let workers = workers.clamp(1, items.len().max(1));
+
+        let mut buffer = Vec::<u8>::new();
+        if workers == 1 {
+            self.read_positions(items, &mut buffer)?;
+        } else {
+            let parts = thread::scope(|scope| {
+                let handles: Vec<_> = items.chunks(items.len().div_ceil(workers))
+                    .map(|part| scope.spawn(move || {
+                        let mut buf = Vec::<u8>::new();
+                        self.read_positions(part, &mut buf).map(|_| buf)
+                    }))
+                    .collect();
+                handles.into_iter()
+                    .map(|handle| handle.join().unwrap_or_else(|err| panic::resume_unwind(err)))
+                    .collect::<Vec<_>>()
+            });
+            for part in parts {
+                buffer.append(&mut part?);
+            }
+        }
+
+        bytes_to_audio(&buffer, out)
+    }
+
+    fn read_positions(&self, items: &[ReadPosition], out: &mut Vec<u8>) -> Result<(), AudioError> {
+        for item in items {
+            AudioLoader::load_block_slice(self, item, out)?;
+        }
+        Ok(())
+    }
+
+    /// Load `n` frames from frame `start` on.
+    ///
+    /// With `clip`, frames count from the start of that clip at the rate
+    /// of the clip. Otherwise they count on the project timeline, which
+    /// starts at time zero, at the project rate. The frames must lie
+    /// within one clip, and without `clip` it must run at the project rate.
+    pub fn load_frames(&self, start: u64, n: u64, clip: Option<usize>) -> Result<Vec<f32>, AudioError> {
+        let clips = self.waveclips.as_deref().unwrap_or(&[]);
+        let (clip_idx, rel) = match clip {
+            Some(idx) => (idx, start),
+            None => {
+                let idx = self.clip_idx_from_frame(start);
+                let first = clips.get(idx).map_or(0, |x| self.clip_start_frame(x));
+                if start < first + clips.get(idx).map_or(0, |x| x.play_frames(self.fps).0) {
+                    return Err(AudioError::OutOfRange);
+                }
+                (idx, start - first)
+            }
+        };
+
+        let seq = match clips.get(clip_idx).and_then(|x| x.sequences.as_ref()) {
+            Some(seq) => seq,
+            None => return Err(AudioError::OutOfRange)
+        };
+        if clip.is_none() && clips[clip_idx].rate_or(self.fps) != self.fps {
+            return Err(AudioError::RateMismatch);
+        }
+        let end = if clip.is_none() { clips[clip_idx].play_frames(self.fps).1 } else { seq.numsamples };
+        if rel.checked_add(n).is_none_or(|stop| stop > end) {
+            return Err(AudioError::OutOfRange);
+        }
+
+        let mut out = Vec::<f32>::with_capacity(n as usize);
+        if n > 0 {
+            self.read_items(&self.frame_range(clip_idx, rel, n)?, self.pool.max_size(), &mut out)?;
+        }
+        Ok(out)
+    }
+
+    /// Number of frames from time zero to the end of the last clip.
+    pub fn num_frames(&self) -> u64 {
+        self.waveclips.as_deref().unwrap_or(&[]).iter()
+            .filter(|x| x.sequences.is_some())
+            .map(|x| self.clip_start_frame(x) + x.play_frames(self.fps).1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Load several `(start, stop)` segments in parallel.
+    ///
+    /// Returns the samples of each segment in the order of `segments`.
+    pub fn load_many(&self, segments: &[(f64, f64)]) -> Result<Vec<Vec<f32>>, AudioError> {
+        let workers = self.pool.max_size().clamp(1, segments.len().max(1));
+        let next = AtomicUsize::new(0);
+
+        let mut results: Vec<(usize, Result<Vec<f32>, AudioError>)> = thread::scope(|scope| {
+            let handles: Vec<_> = (0..workers).map(|_| scope.spawn(|| {
+                let mut done = Vec::new();
+                loop {
+                    let i = next.fetch_add(1, AtomicOrdering::Relaxed);
+                    let Some(&(start, stop)) = segments.get(i) else { break };
+                    let mut samples = Vec::<f32>::new();
+                    done.push((i, self.read_slice(start, stop, 1, &mut samples).map(|_| samples)));
+                }
+                done
+            })).collect();
+            handles.into_iter()
+                .flat_map(|handle| handle.join().unwrap_or_else(|err| panic::resume_unwind(err)))
+                .collect()
+        });
+
+        results.sort_by_key(|(i, _)| *i);
+        results.into_iter().map(|(_, samples)| samples).collect()
+    }
+
+    /// Run the integrity checks on the sample blocks of this project.
+    pub fn check(&self) -> Result<CheckReport, AudioError> {
+        let con = self.pool.get().map_err(|_| AudioError::ReadFailed)?;
+        Ok(check::check(&con, self.waveclips.as_deref().unwrap_or(&[])))
     }
 }
 
@@ -176,16 +479,314 @@ impl Project {
         self.__str__()
     }
 
-    fn load_audio(&self, start: f64, stop: f64) -> PyResult<Vec<f32>> {
+    #[getter]
+    fn mode(&self) -> &'static str {
+        self.mode.name()
+    }
+
+    #[getter]
+    fn source(&self) -> &'static str {
+        self.source.name()
+    }
+
+    // Describe what the loaded data reflects.
+    //
+    // "saved" means the last explicit save. "saved (unsaved changes
+    // pending)" means Audacity holds newer state in the autosave table.
+    // "autosave" means the document was decoded from the autosave table.
+    // "checkpoint" means the file was opened immutable while a writer
+    // was active, so changes still in the WAL are not visible.
+    #[getter]
+    fn state(&self) -> &'static str {
+        if self.mode == OpenMode::Immutable && self.writer_active {
+            "checkpoint"
+        } else if self.source == DocSource::Autosave {
+            "autosave"
+        } else if self.unsaved_changes {
+            "saved (unsaved changes pending)"
+        } else {
+            "saved"
+        }
+    }
+
+    // Select labels by title, pattern, duration, time window, track and
+    // kind ("point" or "region").
+    #[pyo3(signature = (title=None, regex=None, min_duration=None, max_duration=None,
+        within=None, track=None, kind=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn find_labels(&self, title: Option<String>, regex: Option<&str>, min_duration: Option<f64>,
+        max_duration: Option<f64>, within: Option<(f64, f64)>, track: Option<String>,
+        kind: Option<&str>) -> PyResult<Vec<Label>> {
+
+        let regex = match regex.map(Regex::new) {
+            Some(Ok(re)) => Some(re),
+            Some(Err(err)) => return Err(PyValueError::new_err(format!("Bad pattern: {}", err))),
+            None => None
+        };
+        let kind = match kind {
+            Some(name) => match LabelKind::parse(name) {
+                Some(kind) => Some(kind),
+                None => return Err(PyValueError::new_err(format!("Unknown label kind '{}'.", name)))
+            },
+            None => None
+        };
+
+        let query = LabelQuery { title, regex, min_duration, max_duration, within, track, kind };
+        Ok(query.filter(self.labels.as_deref().unwrap_or(&[])))
+    }
+
+    // Analyze the labels for overlaps, duplicates, zero-length regions,
+    // labels outside of the audio and titles not in `vocabulary`.
+    #[pyo3(signature = (expect_regions=false, vocabulary=None))]
+    fn label_report(&self, expect_regions: bool, vocabulary: Option<HashSet<String>>) -> LabelReport {
+        let extents = clip_extents(self.waveclips.as_deref().unwrap_or(&[]));
+        let options = AnalysisOptions { expect_regions, vocabulary };
+        analysis::analyze(self.labels.as_deref().unwrap_or(&[]), &extents, &options)
+    }
+
+    // Labels as a pyarrow Table, or a pandas DataFrame when pandas is
+    // installed. `backend` ("arrow" or "pandas") overrides the choice.
+    #[pyo3(signature = (backend=None))]
+    fn labels_table(&self, py: Python<'_>, backend: Option<&str>) -> PyResult<PyObject> {
+        self.table("labels")?.to_python(py, backend)
+    }
+
+    // One row per wave clip.
+    #[pyo3(signature = (backend=None))]
+    fn clips_table(&self, py: Python<'_>, backend: Option<&str>) -> PyResult<PyObject> {
+        self.table("clips")?.to_python(py, backend)
+    }
+
+    // One row per wave block.
+    #[pyo3(signature = (backend=None))]
+    fn blocks_table(&self, py: Python<'_>, backend: Option<&str>) -> PyResult<PyObject> {
+        self.table("blocks")?.to_python(py, backend)
+    }
+
+    // Write the "labels", "clips" or "blocks" table to a Parquet file.
+    #[cfg(feature = "parquet")]
+    #[pyo3(signature = (path, table="labels"))]
+    fn write_parquet(&self, py: Python<'_>, path: &str, table: &str) -> PyResult<()> {
+        let table = self.table(table)?;
+        py.allow_threads(|| table::parquet::write_parquet(&table, path))
+            .map_err(|err| PyIOError::new_err(format!("Could not write '{}': {}", path, err)))
+    }
+
+    // Return the first label track called `name`.
+    pub fn label_track(&self, name: &str) -> Option<LabelTrack> {
+        self.label_tracks.as_ref()?.iter().find(|track| track.name == name).cloned()
+    }
+
+    // Return the first wave clip called `name`.
+    pub fn clip(&self, name: &str) -> Option<WaveClip> {
+        self.waveclips.as_ref()?.iter().find(|clip| clip.name.as_deref() == Some(name)).cloned()
+    }
+
+    // Run the integrity checks on the sample blocks of this project.
+    #[pyo3(name = "check")]
+    fn py_check(&self) -> PyResult<CheckReport> {
+        self.check().map_err(|_| PyIOError::new_err("Could not connect to project"))
+    }
+
+    // Read and decode without holding the GIL, so other Python threads
+    // can load from this or other projects meanwhile.
+    //
+    // Audio is returned at the rate of its track, or converted to
+    // `target_rate` if given. On stretched clips the raw samples are
+    // returned, unless `render_stretch` applies the clip's stretch ratio
+    // and pitch shift. With `time_warp`, the audio is played through the
+    // time track, as Audacity exports it. Trimmed audio and time outside
+    // of the clip are silent.
+    #[pyo3(signature = (start, stop, target_rate=None, render_stretch=false, time_warp=false))]
+    fn load_audio(&self, py: Python<'_>, start: f64, stop: f64, target_rate: Option<u32>,
+        render_stretch: bool, time_warp: bool) -> PyResult<Vec<f32>> {
         let mut samples = Vec::<f32>::new();
-        if let Err(_) = AudioLoader::load_slice(self, start, stop, &mut samples) {
-            return Err(PyIOError::new_err("Could not read audio"));
+        match py.allow_threads(|| AudioLoader::load_slice(self, start, stop, &mut samples)) {
+            Ok(()) => {},
+            Err(AudioError::MultipleClips) => return Err(PyValueError::new_err(format!(
+                "Audio from {} to {} stretches over more than one clip.", start, stop))),
+            Err(_) => return Err(PyIOError::new_err("Could not read audio"))
+        }
+        if render_stretch {
+            samples = py.allow_threads(|| self.render_stretch(start, &samples));
+        }
+        if let (true, Some(track)) = (time_warp, &self.time_track) {
+            samples = py.allow_threads(|| timewarp::render(track, &samples, self.rate_at(start), start));
+        }
+        match target_rate {
+            Some(rate) => Ok(py.allow_threads(|| resample(&samples, self.rate_at(start), rate))),
+            None => Ok(samples)
         }
-        Ok(samples)
     }
 
-    fn load_label(&self, label: &Label) -> PyResult<Vec<f32>> {
-        Ok(self.load_audio(label.t, label.t1).unwrap())
+    // Version of the project file format.
+    #[getter]
+    fn version(&self) -> Option<String> {
+        self.attributes.version.clone()
+    }
+
+    // Project format version from the file header, e.g. "3.0.0.0".
+    #[getter]
+    fn format_version(&self) -> String {
+        self.compatibility.format_version.clone()
+    }
+
+    // Version of Audacity that saved the project.
+    #[getter]
+    fn audacity_version(&self) -> Option<String> {
+        self.attributes.audacityversion.clone()
+    }
+
+    // Start and stop of the time selection saved with the project, or
+    // None if nothing was selected.
+    #[getter]
+    fn selection(&self) -> Option<(f64, f64)> {
+        if self.attributes.has_selection() {
+            Some((self.attributes.sel0, self.attributes.sel1))
+        } else {
+            None
+        }
+    }
+
+    // Load the audio of the saved selection.
+    #[pyo3(signature = (target_rate=None, render_stretch=false))]
+    fn load_selection(&self, py: Python<'_>, target_rate: Option<u32>, render_stretch: bool) -> PyResult<Vec<f32>> {
+        match self.selection() {
+            Some((start, stop)) => self.load_audio(py, start, stop, target_rate, render_stretch, false),
+            None => Err(PyValueError::new_err("The project has no saved selection."))
+        }
+    }
+
+    // Metadata tags (Title, Artist, Comments, custom keys) in document
+    // order. Assigning a dict rewrites the tags in the project file,
+    // which requires mode "readwrite".
+    #[getter(metadata)]
+    fn py_metadata<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new_bound(py);
+        for (key, value) in self.metadata.iter() {
+            dict.set_item(key, value)?;
+        }
+        Ok(dict)
+    }
+
+    #[setter(metadata)]
+    fn set_metadata(&mut self, py: Python<'_>, value: Bound<'_, PyDict>) -> PyResult<()> {
+        if self.mode != OpenMode::ReadWrite {
+            return Err(PyValueError::new_err("Project is not open in 'readwrite' mode."));
+        }
+        let mut tags = Vec::<(String, String)>::new();
+        for (key, val) in value.iter() {
+            tags.push((key.extract()?, val.extract()?));
+        }
+        let table = self.source.table();
+        py.allow_threads(|| metadata::write_metadata(&*self.pool.get()?, table, &tags))
+            .map_err(|err| PyIOError::new_err(format!("Could not write metadata: {}", err)))?;
+        self.metadata = tags;
+        Ok(())
+    }
+
+    // Write the audio between `start` and `stop` (default: all of it) to
+    // a WAV or FLAC file, chosen by the extension of `path`, with the
+    // metadata tags.
+    #[pyo3(signature = (path, start=None, stop=None))]
+    fn export_audio(&self, py: Python<'_>, path: &str, start: Option<f64>, stop: Option<f64>) -> PyResult<()> {
+        let start = start.unwrap_or(0f64);
+        let stop = stop.unwrap_or_else(|| clip_extents(self.waveclips.as_deref().unwrap_or(&[]))
+            .iter().map(|x| x.1).fold(0f64, f64::max));
+        let extension = std::path::Path::new(path).extension()
+            .and_then(|ext| ext.to_str()).unwrap_or("").to_ascii_lowercase();
+        let write = match extension.as_str() {
+            "wav" => export::write_wav_with_info,
+            "flac" => export::write_flac,
+            _ => return Err(PyValueError::new_err(format!("Unknown audio format '{}'.", extension)))
+        };
+        let samples = self.load_audio(py, start, stop, None, false, false)?;
+        let rate = self.rate_at(start);
+        py.allow_threads(|| write(path, rate, &samples, &self.metadata))
+            .map_err(|err| PyIOError::new_err(format!("Could not write '{}': {}", path, err)))
+    }
+
+    // Playback time at which timeline position `t` is heard. Equals `t`
+    // without a time track.
+    fn warp_time(&self, t: f64) -> f64 {
+        match &self.time_track {
+            Some(track) => timewarp::warp_time(track, t),
+            None => t
+        }
+    }
+
+    // Timeline position heard at playback time `t`.
+    fn unwarp_time(&self, t: f64) -> f64 {
+        match &self.time_track {
+            Some(track) => timewarp::unwarp_time(track, t),
+            None => t
+        }
+    }
+
+    // Distinct sample rates of the wave tracks.
+    #[getter]
+    fn rates(&self) -> Vec<u32> {
+        let mut out: Vec<u32> = self.waveclips.as_deref().unwrap_or(&[]).iter()
+            .map(|clip| clip.rate_or(self.fps)).collect();
+        out.sort();
+        out.dedup();
+        out
+    }
+
+    // Load `n_frames` frames from `start_frame` on, counted on the project
+    // timeline or, with `clip`, from the start of that clip.
+    #[pyo3(name = "load_frames", signature = (start_frame, n_frames, clip=None))]
+    fn py_load_frames(&self, py: Python<'_>, start_frame: u64, n_frames: u64,
+        clip: Option<usize>) -> PyResult<Vec<f32>> {
+        match py.allow_threads(|| self.load_frames(start_frame, n_frames, clip)) {
+            Ok(out) => Ok(out),
+            Err(AudioError::OutOfRange) => Err(PyValueError::new_err(format!(
+                "{} frames from frame {} are not within one clip.", n_frames, start_frame))),
+            Err(AudioError::RateMismatch) => Err(PyValueError::new_err(
+                "Clip rate differs from the project rate, pass `clip`.")),
+            Err(_) => Err(PyIOError::new_err("Could not read audio"))
+        }
+    }
+
+    #[getter(num_frames)]
+    fn py_num_frames(&self) -> u64 {
+        self.num_frames()
+    }
+
+    // Start and stop frame of `label` at the project rate.
+    #[pyo3(signature = (label, rounding="nearest"))]
+    fn label_frames(&self, label: &Label, rounding: &str) -> PyResult<(u64, u64)> {
+        match Rounding::parse(rounding) {
+            Some(rounding) => Ok(label.to_frames(self.fps, rounding)),
+            None => Err(PyValueError::new_err(format!("Unknown rounding '{}'.", rounding)))
+        }
+    }
+
+    // Load several `(start, stop)` segments in parallel, in the given order.
+    #[pyo3(name = "load_many")]
+    fn py_load_many(&self, py: Python<'_>, segments: Vec<(f64, f64)>) -> PyResult<Vec<Vec<f32>>> {
+        match py.allow_threads(|| self.load_many(&segments)) {
+            Ok(out) => Ok(out),
+            Err(AudioError::MultipleClips) => Err(PyValueError::new_err(
+                "A segment stretches over more than one clip.")),
+            Err(_) => Err(PyIOError::new_err("Could not read audio"))
+        }
+    }
+
+    // Load the audio of `label`. With `bandpass`, the audio is filtered
+    // to the frequency range of the label, if it has one.
+    #[pyo3(signature = (label, bandpass=false, target_rate=None, render_stretch=false))]
+    fn load_label(&self, py: Python<'_>, label: &Label, bandpass: bool, target_rate: Option<u32>,
+        render_stretch: bool) -> PyResult<Vec<f32>> {
+        let mut samples = self.load_audio(py, label.t, label.t1, None, render_stretch, false)?;
+        let rate = self.rate_at(label.t);
+        if bandpass && label.has_band() {
+            samples = py.allow_threads(|| filter::bandpass(&samples, rate, label.f0, label.f1));
+        }
+        match target_rate {
+            Some(target) => Ok(py.allow_threads(|| resample(&samples, rate, target))),
+            None => Ok(samples)
+        }
     }
 }
 
@@ -203,21 +804,7 @@ impl AudioProcessor for Project {
 
 impl AudioLoader for Project {
     fn load_slice(&self, start: f64, stop: f64, out: &mut Vec<f32>) -> Result<(), AudioError> {
-
-        let mut buffer = Vec::<u8>::new();
-        let items = self.block_range(start, stop);
-
-        for item in items {
-            match AudioLoader::load_block_slice(self, &item, &mut buffer) {
-                Ok(()) => {},
-                Err(err) => {
-                    panic!("ERROR: Could not read block, because {:?}.", err);
-                }
-            }
-        }
-
-        bytes_to_audio(&buffer, out).unwrap();
-        Ok(())
+        self.read_slice(start, stop, self.pool.max_size(), out)
     }
 
     // Read chunk from waveblock.
@@ -225,9 +812,10 @@ impl AudioLoader for Project {
     // Chunk size is determined by `item`.
     fn load_block_slice(&self, item: &ReadPosition, out: &mut Vec<u8>) -> Result<(), AudioError> {
 
-        let mut blob = self.con.blob_open(DatabaseName::Main, "sampleblocks",
+        let con = self.pool.get().map_err(|_| AudioError::ReadFailed)?;
+        let mut blob = con.blob_open(DatabaseName::Main, "sampleblocks",
             "samples", item.block_id as i64, true)
-            .expect("Cannot read blob");
+            .map_err(|_| AudioError::ReadFailed)?;
 
 
         let mut buffer = Vec::<u8>::with_capacity(blob.size() as usize);
@@ -250,9 +838,10 @@ impl AudioLoader for Project {
     }
 
     fn load_wave_block(&self, block_id: u16) -> Result<Vec::<u8>, AudioError> {
-        let mut blob = self.con.blob_open(DatabaseName::Main, "sampleblocks",
+        let con = self.pool.get().map_err(|_| AudioError::ReadFailed)?;
+        let mut blob = con.blob_open(DatabaseName::Main, "sampleblocks",
             "samples", block_id as i64, true)
-            .expect("Cannot read blob");
+            .map_err(|_| AudioError::ReadFailed)?;
         let mut buffer = Vec::<u8>::with_capacity(blob.len());
 
         match blob.read_to_end(&mut buffer) {
@@ -267,15 +856,15 @@ impl AudioLoader for Project {
     }
 }
 
-pub fn bytes_to_audio(buffer: &[u8], out: &mut Vec<f32>) ->  Result<(), ()> {
-    let (pre, samples, post) = unsafe { buffer.align_to::<f32>() };
-    let overspilled = pre.len() + post.len();
-    if let Ordering::Greater = overspilled.cmp(&0) {
-        return Err(());
+/// Convert little-endian 32 bit float samples, at any alignment of
+/// `buffer`, replacing the contents of `out`.
+pub fn bytes_to_audio(buffer: &[u8], out: &mut Vec<f32>) -> Result<(), AudioError> {
+    if !buffer.len().is_multiple_of(4) {
+        return Err(AudioError::ReadFailed);
     }
 
-    out.resize(samples.len(), 0f32);
-    out.copy_from_slice(samples);
+    out.clear();
+    out.extend(buffer.chunks_exact(4).map(LittleEndian::read_f32));
     Ok(())
 }
 
@@ -283,6 +872,458 @@ pub fn bytes_to_audio(buffer: &[u8], out: &mut Vec<f32>) ->  Result<(), ()> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rusqlite::Connection;
+    use crate::audacity::serializer::ProjectSerializer;
+    use crate::writer::{create, create_with_rates, write_labeltrack, write_project_start,
+        write_waveclip_audio, write_waveclip_start, write_wavetrack_start, ProjectWriter};
+
+    fn temp_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_file(&path);
+        path.to_str().unwrap().to_string()
+    }
+
+    // Write a project to `name` in the temp directory, `body` writing its
+    // document and blocks, and open it.
+    fn open_written(name: &str, body: impl FnOnce(&ProjectWriter, &mut ProjectSerializer))
+        -> Result<Project, rusqlite::Error> {
+        let path = temp_path(name);
+        let writer = ProjectWriter::create(&path).unwrap();
+        let mut ser = ProjectSerializer::new();
+        body(&writer, &mut ser);
+        let (dict, doc) = ser.finish();
+        writer.write_doc(&dict, &doc).unwrap();
+        drop(writer);
+        Project::open(&path)
+    }
+
+    #[test]
+    fn unaligned_samples() {
+        let mut bytes = vec![0u8];
+        for val in [0.5f32, -1f32, 0.25] {
+            bytes.extend_from_slice(&val.to_le_bytes());
+        }
+        let mut out = vec![1f32; 5];
+        bytes_to_audio(&bytes[1..], &mut out).unwrap();
+        assert_eq!(out, vec![0.5, -1f32, 0.25]);
+        assert!(matches!(bytes_to_audio(&bytes[..6], &mut out), Err(AudioError::ReadFailed)));
+    }
+
+    #[test]
+    fn label_band() {
+        let path = temp_path("aup3conv-label-band.aup3");
+        let labels = vec![
+            Label::new(0f64, 0.5, "band".to_string()).with_band(Some(200f64), Some(900f64)),
+            Label::new(0.5, 1f64, "plain".to_string()),
+        ];
+        create(&path, 8000, &[vec![0f32; 8000]], &labels).unwrap();
+
+        let parsed = Project::open(&path).unwrap().labels.unwrap();
+        assert_eq!((parsed[0].f0, parsed[0].f1), (Some(200f64), Some(900f64)));
+        assert_eq!((parsed[1].f0, parsed[1].f1), (None, None));
+    }
+
+    #[test]
+    fn label_tracks() {
+        let project = open_written("aup3conv-label-tracks.aup3", |_, ser| {
+            write_project_start(ser, 8000);
+            write_labeltrack(ser, "species", &[Label::new(0f64, 1f64, "owl".to_string())]);
+            write_labeltrack(ser, "noise", &[Label::new(0f64, 2f64, "wind".to_string()),
+                Label::new(3f64, 4f64, "rain".to_string())]);
+            ser.end_tag("project");
+        }).unwrap();
+        assert_eq!(project.labels.as_ref().unwrap().len(), 3);
+        let tracks = project.label_tracks.as_ref().unwrap();
+        assert_eq!(tracks.iter().map(|x| x.name.as_str()).collect::<Vec<_>>(), vec!["species", "noise"]);
+        let labels = project.labels.as_ref().unwrap();
+        assert_eq!((labels[2].track.as_deref(), labels[2].index), (Some("noise"), Some(1)));
+        let noise = project.label_track("noise").unwrap();
+        assert_eq!(noise.labels.iter().map(|x| x.title.as_str()).collect::<Vec<_>>(), vec!["wind", "rain"]);
+        assert!(project.label_track("call type").is_none());
+
+        // a label outside of any label track
+        let project = open_written("aup3conv-orphan-label.aup3", |_, ser| {
+            write_project_start(ser, 8000);
+            ser.start_tag("label");
+            ser.write_double("t", 0f64);
+            ser.write_double("t1", 1f64);
+            ser.write_str("title", "owl");
+            ser.end_tag("label");
+            ser.end_tag("project");
+        });
+        assert!(project.is_err());
+    }
+
+    #[test]
+    fn load_frames() {
+        let path = temp_path("aup3conv-frames.aup3");
+        let samples: Vec<f32> = (0..600000).map(|i| i as f32).collect();
+        create(&path, 10000, std::slice::from_ref(&samples), &[]).unwrap();
+        let project = Project::open(&path).unwrap();
+        assert_eq!(project.num_frames(), 600000);
+
+        // back to back slices, one ending on a block boundary
+        let mut start = 0u64;
+        for n in [100000u64, 162144, 3, 337853] {
+            let out = project.load_frames(start, n, None).unwrap();
+            assert!(out == samples[start as usize..(start + n) as usize], "slice at {}", start);
+            start += n;
+        }
+        assert_eq!(project.load_frames(10, 0, Some(0)).unwrap().len(), 0);
+        assert!(matches!(project.load_frames(599999, 2, None), Err(AudioError::OutOfRange)));
+        assert!(matches!(project.load_frames(1, u64::MAX, None), Err(AudioError::OutOfRange)));
+
+        let label = Label::new(0.00015, 0.00035, "x".to_string());
+        assert_eq!(label.to_frames(10000, Rounding::Floor), (1, 3));
+        assert_eq!(label.to_frames(10000, Rounding::Ceil), (2, 4));
+    }
+
+    #[test]
+    fn track_rates() {
+        let path = temp_path("aup3conv-rates.aup3");
+        let samples: Vec<f32> = (0..32000).map(|i| i as f32).collect();
+        create_with_rates(&path, 8000, &[vec![0f32; 8000], samples.clone()], &[8000, 16000], &[]).unwrap();
+        let project = Project::open(&path).unwrap();
+        assert_eq!(project.rate_at(0.5), 16000);
+
+        let mut out = Vec::<f32>::new();
+        project.load_slice(1f64, 1.5, &mut out).unwrap();
+        assert_eq!(out, samples[16000..24000]);
+        assert!(matches!(project.load_frames(0, 10, None), Err(AudioError::RateMismatch)));
+        assert_eq!(project.load_frames(16000, 2, Some(1)).unwrap(), vec![16000f32, 16001f32]);
+        assert_eq!(clip_extents(project.waveclips.as_deref().unwrap()), vec![(0f64, 1f64), (0f64, 2f64)]);
+    }
+
+    #[test]
+    fn stretched_clip() {
+        let samples: Vec<f32> = (0..8000).map(|i| i as f32).collect();
+        let project = open_written("aup3conv-stretch.aup3", |writer, ser| {
+            write_project_start(ser, 8000);
+            write_wavetrack_start(ser, "Audio 1", 8000);
+            write_waveclip_start(ser, 0f64, "Audio 1");
+            ser.write_double("clipStretchRatio", 2f64);
+            ser.write_int("centShift", 0);
+            write_waveclip_audio(writer, ser, &samples).unwrap();
+            ser.end_tag("wavetrack");
+            ser.end_tag("project");
+        }).unwrap();
+        let clips = project.waveclips.as_deref().unwrap();
+        assert_eq!(clips[0].stretch_ratio, 2f64);
+        assert_eq!(clip_extents(clips), vec![(0f64, 2f64)]);
+
+        let mut out = Vec::<f32>::new();
+        project.load_slice(1f64, 1.5, &mut out).unwrap();
+        assert_eq!(out, samples[4000..6000]);
+        assert_eq!(project.render_stretch(1f64, &out).len(), 4000);
+    }
+
+    #[test]
+    fn project_attributes() {
+        let path = temp_path("aup3conv-attributes.aup3");
+        create(&path, 8000, &[vec![0f32; 100]], &[]).unwrap();
+        let attributes = Project::open(&path).unwrap().attributes;
+        assert_eq!(attributes.version.as_deref(), Some("1.3.0"));
+        assert_eq!(attributes.audacityversion.as_deref(), Some("3.0.0"));
+        assert_eq!(attributes.rate, 8000f64);
+        assert!(!attributes.has_selection());
+
+        let project = open_written("aup3conv-selection.aup3", |_, ser| {
+            ser.start_tag("project");
+            ser.write_str("version", "1.3.0");
+            ser.write_double("sel0", 0.25);
+            ser.write_double("sel1", 0.5);
+            ser.write_double("selLow", 100f64);
+            ser.write_double("selHigh", -1f64);
+            ser.write_double("zoom", 44.1);
+            ser.write_double("rate", 8000f64);
+            ser.write_str("snapto", "on");
+            ser.end_tag("project");
+        }).unwrap();
+        let attributes = &project.attributes;
+        assert_eq!((attributes.sel0, attributes.sel1), (0.25, 0.5));
+        assert_eq!((attributes.sel_low, attributes.sel_high), (Some(100f64), None));
+        assert_eq!(attributes.zoom, 44.1);
+        assert_eq!(attributes.snapto.as_deref(), Some("on"));
+        assert_eq!(attributes.audacityversion, None);
+        assert_eq!(project.selection(), Some((0.25, 0.5)));
+    }
+
+    #[test]
+    fn realtime_effects() {
+        let project = open_written("aup3conv-effects.aup3", |_, ser| {
+            write_project_start(ser, 8000);
+            for (name, active) in [("Audio 1", true), ("Audio 2", false)] {
+                write_wavetrack_start(ser, name, 8000);
+                ser.start_tag("effects");
+                ser.write_bool("active", active);
+                ser.start_tag("effect");
+                ser.write_str("id", "Built-in Effect: Reverb");
+                ser.write_str("version", "3.4.2");
+                ser.write_bool("active", true);
+                ser.start_tag("parameters");
+                ser.start_tag("parameter");
+                ser.write_str("name", "RoomSize");
+                ser.write_str("value", "75");
+                ser.end_tag("parameter");
+                ser.end_tag("parameters");
+                ser.end_tag("effect");
+                ser.end_tag("effects");
+                ser.end_tag("wavetrack");
+            }
+            // an integer flag on the stack, as on the effects
+            ser.start_tag("effects");
+            ser.write_int("active", 2);
+            ser.start_tag("effect");
+            ser.write_str("id", "Built-in Effect: Compressor");
+            ser.write_int("active", 0);
+            ser.end_tag("effect");
+            ser.start_tag("effect");
+            ser.write_str("id", "Built-in Effect: Limiter");
+            ser.write_bool("active", true);
+            ser.end_tag("effect");
+            ser.end_tag("effects");
+            ser.end_tag("project");
+        }).unwrap();
+        assert_eq!(project.tracks.len(), 2);
+        let effect = &project.tracks[0].effects[0];
+        assert_eq!(effect.plugin_id, "Built-in Effect: Reverb");
+        assert_eq!(effect.version.as_deref(), Some("3.4.2"));
+        assert!(effect.active);
+        assert_eq!(effect.parameters, vec![("RoomSize".to_string(), "75".to_string())]);
+        assert!(!project.tracks[1].effects[0].active);
+
+        assert_eq!(project.master_effects.len(), 2);
+        assert_eq!(project.master_effects[0].plugin_id, "Built-in Effect: Compressor");
+        assert!(!project.master_effects[0].active);
+        assert!(project.master_effects[1].active);
+    }
+
+    #[test]
+    fn note_track() {
+        let project = open_written("aup3conv-notetrack.aup3", |_, ser| {
+            write_project_start(ser, 8000);
+            ser.start_tag("notetrack");
+            ser.write_str("name", "Melody");
+            ser.write_double("offset", 1.5);
+            ser.write_str("data", "#track 0\nT0 V0 K64 P64 U0.5 L100\nT0.5 V0 K65 P65 U0.5 L100\n");
+            ser.end_tag("notetrack");
+            ser.end_tag("project");
+        }).unwrap();
+        let track = &project.note_tracks[0];
+        assert_eq!((track.name.as_str(), track.offset), ("Melody", 1.5));
+        assert_eq!(track.num_notes(), 2);
+
+        let midi = temp_path("aup3conv-notetrack.mid");
+        track.write_midi(&midi).unwrap();
+        let bytes = std::fs::read(&midi).unwrap();
+        // first note at 1.5 s, 1440 ticks: 0x8B 0x20
+        let events = &bytes[14 + 8 + 11 + 8..];
+        assert_eq!(&events[..5], &[0x8B, 0x20, 0x90, 64, 100]);
+    }
+
+    #[test]
+    fn time_track() {
+        let project = open_written("aup3conv-timetrack.aup3", |_, ser| {
+            write_project_start(ser, 8000);
+            write_wavetrack_start(ser, "Audio 1", 8000);
+            // an envelope outside the time track, which must be ignored
+            ser.start_tag("envelope");
+            ser.write_int("numpoints", 1);
+            ser.start_tag("controlpoint");
+            ser.write_double("t", 0.5);
+            ser.write_double("val", 0.1);
+            ser.end_tag("controlpoint");
+            ser.end_tag("envelope");
+            ser.end_tag("wavetrack");
+            ser.start_tag("timetrack");
+            ser.write_str("name", "Time Track");
+            ser.write_double("rangelower", 0.5);
+            ser.write_double("rangeupper", 2f64);
+            ser.write_bool("interpolatelog", false);
+            ser.start_tag("envelope");
+            ser.write_int("numpoints", 2);
+            for (t, val) in [(1f64, 2f64), (0f64, 2f64)] {
+                ser.start_tag("controlpoint");
+                ser.write_double("t", t);
+                ser.write_double("val", val);
+                ser.end_tag("controlpoint");
+            }
+            ser.end_tag("envelope");
+            ser.end_tag("timetrack");
+            ser.end_tag("project");
+        }).unwrap();
+        let track = project.time_track.as_ref().unwrap();
+        assert_eq!(track.points, vec![(0f64, 2f64), (1f64, 2f64)]);
+        assert_eq!((track.range_lower, track.range_upper), (0.5, 2f64));
+        assert_eq!(project.warp_time(3f64), 1.5);
+        assert!((project.unwarp_time(1.5) - 3f64).abs() < 1e-9);
+        assert!(project.compatibility.unknown_elements.is_empty());
+    }
+
+    #[test]
+    fn clip_names() {
+        let samples: Vec<f32> = (0..800).map(|i| i as f32 / 800f32).collect();
+        let project = open_written("aup3conv-clip-names.aup3", |writer, ser| {
+            write_project_start(ser, 8000);
+            write_wavetrack_start(ser, "Audio 1", 8000);
+            write_waveclip_start(ser, 0f64, "Take 1");
+            write_waveclip_audio(writer, ser, &[0.25f32; 800]).unwrap();
+            ser.start_tag("waveclip");
+            ser.write_double("offset", 1f64);
+            ser.write_double("trimLeft", 0.01);
+            ser.write_double("trimRight", 0.02);
+            ser.write_str("name", "Take 2");
+            ser.write_int("colorindex", 3);
+            write_waveclip_audio(writer, ser, &samples).unwrap();
+            ser.end_tag("wavetrack");
+            ser.end_tag("project");
+        }).unwrap();
+        assert!(project.clip("Take 3").is_none());
+        let clip = project.clip("Take 2").unwrap();
+        assert_eq!(clip.colorindex, Some(3));
+        assert_eq!((clip.trim_left, clip.trim_right), (0.01, 0.02));
+        assert!((clip.play_start() - 1.01).abs() < 1e-12);
+        assert!((clip.play_end() - 1.08).abs() < 1e-12);
+        assert!((clip.duration() - 0.07).abs() < 1e-12);
+        let extents = clip_extents(project.waveclips.as_deref().unwrap());
+        assert_eq!(extents, vec![(0f64, 0.1), (clip.play_start(), clip.play_end())]);
+
+        assert_eq!(clip.read_samples(false).unwrap(), samples);
+        assert_eq!(clip.read_samples(true).unwrap(), samples[80..640].to_vec());
+        assert_eq!(project.clip("Take 1").unwrap().read_samples(true).unwrap(), vec![0.25f32; 800]);
+    }
+
+    #[test]
+    fn trimmed_playback() {
+        // the hidden head of the second clip lies under the first one
+        let samples: Vec<f32> = (0..8000).map(|i| i as f32).collect();
+        let project = open_written("aup3conv-trimmed.aup3", |writer, ser| {
+            write_project_start(ser, 8000);
+            write_wavetrack_start(ser, "Audio 1", 8000);
+            write_waveclip_start(ser, 0f64, "Take 1");
+            write_waveclip_audio(writer, ser, &[0.25f32; 8000]).unwrap();
+            ser.start_tag("waveclip");
+            ser.write_double("offset", 0.5);
+            ser.write_double("trimLeft", 0.5);
+            ser.write_double("trimRight", 0.25);
+            write_waveclip_audio(writer, ser, &samples).unwrap();
+            ser.end_tag("wavetrack");
+            ser.end_tag("project");
+        }).unwrap();
+
+        let mut out = Vec::<f32>::new();
+        project.load_slice(0.25, 0.75, &mut out).unwrap();
+        assert_eq!(out, vec![0.25f32; 4000]);
+        project.load_slice(1f64, 1.5, &mut out).unwrap();
+        assert_eq!(out[..2000], samples[4000..6000]);
+        assert_eq!(out[2000..], vec![0f32; 2000]);
+        project.load_slice(1.5, 2f64, &mut out).unwrap();
+        assert_eq!(out, vec![0f32; 4000]);
+
+        assert_eq!(project.num_frames(), 10000);
+        assert_eq!(project.load_frames(7998, 2, None).unwrap(), vec![0.25f32; 2]);
+        assert_eq!(project.load_frames(8000, 2, None).unwrap(), samples[4000..4002]);
+        assert!(matches!(project.load_frames(9999, 2, None), Err(AudioError::OutOfRange)));
+    }
+
+    #[test]
+    fn load_parallel() {
+        let path = temp_path("aup3conv-load-many.aup3");
+        let samples: Vec<f32> = (0..700000).map(|i| (i % 1000) as f32 / 1000f32).collect();
+        create(&path, 10000, std::slice::from_ref(&samples), &[]).unwrap();
+        let project = Project::open(&path).unwrap();
+
+        let mut out = Vec::<f32>::new();
+        project.load_slice(1f64, 65f64, &mut out).unwrap();
+        assert_eq!(out, samples[10000..650000]);
+
+        let segments = [(50f64, 60f64), (0f64, 0.5), (20f64, 30f64)];
+        let many = project.load_many(&segments).unwrap();
+        for ((start, stop), got) in segments.iter().zip(many) {
+            let (a, b) = ((start * 10000f64) as usize, (stop * 10000f64) as usize);
+            assert_eq!(got, samples[a..b]);
+        }
+    }
+
+    #[test]
+    fn missing_block() {
+        let path = temp_path("aup3conv-missing-block.aup3");
+        create(&path, 10000, &[vec![0.5f32; 600000]], &[]).unwrap();
+        Connection::open(&path).unwrap().execute("DELETE FROM sampleblocks WHERE blockid = 2", []).unwrap();
+        let project = Project::open(&path).unwrap();
+
+        assert!(matches!(project.load_frames(300000, 10, None), Err(AudioError::ReadFailed)));
+        assert!(matches!(project.load_many(&[(0f64, 1f64), (20f64, 59f64)]), Err(AudioError::ReadFailed)));
+        assert_eq!(project.load_many(&[(0f64, 1f64)]).unwrap()[0], vec![0.5f32; 10000]);
+    }
+
+    #[test]
+    fn open_autosave() {
+        let path = temp_path("aup3conv-autosave.aup3");
+        let newer = temp_path("aup3conv-autosave-newer.aup3");
+        create(&path, 8000, &[vec![0f32; 800]], &[Label::new(0f64, 0.1, "old".to_string())]).unwrap();
+        create(&newer, 8000, &[vec![0f32; 800]], &[Label::new(0f64, 0.1, "new".to_string())]).unwrap();
+
+        let saved = Project::open_with(&path, OpenMode::ReadOnly, DocSource::Latest).unwrap();
+        assert_eq!(saved.source, DocSource::Saved);
+        let err = Project::open_with(&path, OpenMode::ReadOnly, DocSource::Autosave).err().unwrap();
+        assert_eq!(err.to_string(), "project has no autosave document");
+
+        let con = Connection::open(&path).unwrap();
+        con.execute("ATTACH DATABASE ?1 AS newer", [&newer]).unwrap();
+        con.execute("INSERT INTO autosave SELECT * FROM newer.project", []).unwrap();
+        drop(con);
+
+        let latest = Project::open_with(&path, OpenMode::ReadOnly, DocSource::Latest).unwrap();
+        assert_eq!(latest.source, DocSource::Autosave);
+        assert_eq!(latest.labels.unwrap()[0].title, "new");
+
+        let saved = Project::open(&path).unwrap();
+        assert!(saved.unsaved_changes);
+        assert_eq!(saved.labels.unwrap()[0].title, "old");
+    }
+
+    #[test]
+    fn slice_over_blocks() {
+        let path = temp_path("aup3conv-slice-blocks.aup3");
+        let samples: Vec<f32> = (0..1000000).map(|i| i as f32 / 1000000f32).collect();
+        create(&path, 10000, std::slice::from_ref(&samples), &[]).unwrap();
+        let project = Project::open(&path).unwrap();
+
+        // first and last block partly, the two blocks between them whole
+        let mut out = Vec::<f32>::new();
+        project.load_slice(1f64, 95f64, &mut out).unwrap();
+        assert_eq!(out, samples[10000..950000]);
+    }
+
+    #[test]
+    fn slice_over_clips() {
+        let project = open_written("aup3conv-slice-clips.aup3", |writer, ser| {
+            write_project_start(ser, 8000);
+            write_wavetrack_start(ser, "Audio 1", 8000);
+            for (offset, name) in [(0f64, "Take 1"), (1f64, "Take 2")] {
+                write_waveclip_start(ser, offset, name);
+                write_waveclip_audio(writer, ser, &[0.5f32; 8000]).unwrap();
+            }
+            ser.end_tag("wavetrack");
+            ser.end_tag("project");
+        }).unwrap();
+
+        let mut out = Vec::<f32>::new();
+        assert!(matches!(project.load_slice(0.5, 1.5, &mut out), Err(AudioError::MultipleClips)));
+        assert!(matches!(project.load_many(&[(0.5, 1.5)]), Err(AudioError::MultipleClips)));
+    }
+
+    #[test]
+    fn open_invalid() {
+        let path = temp_path("aup3conv-invalid.aup3");
+        std::fs::write(&path, "not a project").unwrap();
+        assert!(Project::open(&path).is_err());
+
+        let path = temp_path("aup3conv-empty.aup3");
+        Connection::open(&path).unwrap().execute_batch("CREATE TABLE x (y)").unwrap();
+        assert!(Project::open(&path).is_err());
+    }
 
     #[test]
     fn test_load_slice() {
@@ -0,0 +1,118 @@
+//! Filter labels.
+
+use regex::Regex;
+
+use crate::structure::Label;
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LabelKind {
+    /// Labels with `t == t1`.
+    Point,
+
+    /// Labels spanning a time range.
+    Region,
+}
+
+
+impl LabelKind {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "point" => Some(Self::Point),
+            "region" => Some(Self::Region),
+            _ => None
+        }
+    }
+}
+
+
+/// Criteria for selecting labels. Unset criteria match every label.
+#[derive(Debug, Clone, Default)]
+pub struct LabelQuery {
+    /// Exact title.
+    pub title: Option<String>,
+
+    /// Pattern searched for in the title.
+    pub regex: Option<Regex>,
+
+    pub min_duration: Option<f64>,
+    pub max_duration: Option<f64>,
+
+    /// Time window the label has to lie in completely.
+    pub within: Option<(f64, f64)>,
+
+    /// Name of the label track.
+    pub track: Option<String>,
+
+    pub kind: Option<LabelKind>,
+}
+
+
+impl LabelQuery {
+    pub fn matches(&self, label: &Label) -> bool {
+        if let Some(title) = &self.title {
+            if label.title != *title { return false }
+        }
+        if let Some(regex) = &self.regex {
+            if !regex.is_match(&label.title) { return false }
+        }
+        if let Some(min) = self.min_duration {
+            if label.duration() < min { return false }
+        }
+        if let Some(max) = self.max_duration {
+            if label.duration() > max { return false }
+        }
+        if let Some((t0, t1)) = self.within {
+            if label.t < t0 || label.t1 > t1 { return false }
+        }
+        if let Some(track) = &self.track {
+            if label.track.as_ref() != Some(track) { return false }
+        }
+        match self.kind {
+            Some(LabelKind::Point) => label.is_point(),
+            Some(LabelKind::Region) => !label.is_point(),
+            None => true
+        }
+    }
+
+    /// Return the matching labels in their original order.
+    pub fn filter(&self, labels: &[Label]) -> Vec<Label> {
+        labels.iter().filter(|label| self.matches(label)).cloned().collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels() -> Vec<Label> {
+        vec![
+            Label::new(0f64, 1f64, "owl".to_string()).in_track(Some("species".to_string()), 0),
+            Label::new(2f64, 2f64, "owl call".to_string()).in_track(Some("calls".to_string()), 0),
+            Label::new(3f64, 6f64, "wind".to_string()).in_track(Some("noise".to_string()), 0),
+        ]
+    }
+
+    fn titles(query: &LabelQuery) -> Vec<String> {
+        query.filter(&labels()).into_iter().map(|x| x.title).collect()
+    }
+
+    #[test]
+    fn criteria() {
+        let all = LabelQuery::default();
+        assert_eq!(titles(&all).len(), 3);
+
+        let query = LabelQuery { regex: Some(Regex::new("^owl").unwrap()), ..Default::default() };
+        assert_eq!(titles(&query), vec!["owl", "owl call"]);
+
+        let query = LabelQuery { kind: Some(LabelKind::Point), ..Default::default() };
+        assert_eq!(titles(&query), vec!["owl call"]);
+
+        let query = LabelQuery { min_duration: Some(0.5), within: Some((0f64, 5f64)), ..Default::default() };
+        assert_eq!(titles(&query), vec!["owl"]);
+
+        let query = LabelQuery { track: Some("noise".to_string()), ..Default::default() };
+        assert_eq!(titles(&query), vec!["wind"]);
+    }
+}
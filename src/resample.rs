@@ -0,0 +1,179 @@
+//! Sample rate conversion.
+
+use std::f64::consts::PI;
+
+
+/// Zero crossings of the sinc kernel on each side of its center, at the
+/// lower of the two rates.
+const ZERO_CROSSINGS: usize = 32;
+
+/// Shape parameter of the Kaiser window. Gives about 90 dB stop-band
+/// attenuation.
+const KAISER_BETA: f64 = 8.6;
+
+/// Cutoff relative to the Nyquist frequency of the lower rate.
+const ROLLOFF: f64 = 0.95;
+
+/// Largest number of filter phases kept in a table. Rate ratios needing
+/// more phases compute the kernel for each output sample instead.
+const MAX_PHASES: u64 = 4096;
+
+
+/// Band-limited resampler with a Kaiser-windowed sinc kernel.
+pub struct Resampler {
+    from: u64,
+    to: u64,
+
+    // Cutoff relative to the input rate.
+    cutoff: f64,
+
+    // Number of input samples on each side of an output sample.
+    half_width: usize,
+
+    // Kernel values by phase, `2 * half_width` each, if precomputed.
+    table: Option<Vec<Vec<f64>>>,
+}
+
+
+impl Resampler {
+    pub fn new(from: u32, to: u32) -> Self {
+        let g = gcd(from as u64, to as u64);
+        let (from, to) = (from as u64 / g, to as u64 / g);
+        let cutoff = ROLLOFF * (to as f64 / from as f64).min(1f64);
+        let half_width = (ZERO_CROSSINGS as f64 / cutoff).ceil() as usize;
+
+        let mut out = Self { from, to, cutoff, half_width, table: None };
+        if to <= MAX_PHASES {
+            out.table = Some((0..to).map(|phase| out.kernel(phase as f64 / to as f64)).collect());
+        }
+        out
+    }
+
+    /// Convert `samples`. The output has `len * to / from` samples.
+    pub fn process(&self, samples: &[f32]) -> Vec<f32> {
+        if self.from == self.to {
+            return samples.to_vec();
+        }
+
+        let n_out = (samples.len() as u64 * self.to / self.from) as usize;
+        let mut out = Vec::<f32>::with_capacity(n_out);
+        for j in 0..n_out as u64 {
+            // position j * from / to in input samples, split into an
+            // integer index and a phase
+            let index = (j * self.from / self.to) as i64;
+            let phase = j * self.from % self.to;
+            let computed;
+            let kernel = match &self.table {
+                Some(table) => &table[phase as usize],
+                None => {
+                    computed = self.kernel(phase as f64 / self.to as f64);
+                    &computed
+                }
+            };
+
+            let first = index - self.half_width as i64 + 1;
+            let mut acc = 0f64;
+            for (k, h) in kernel.iter().enumerate() {
+                let i = first + k as i64;
+                if i >= 0 && (i as usize) < samples.len() {
+                    acc += samples[i as usize] as f64 * h;
+                }
+            }
+            out.push(acc as f32);
+        }
+        out
+    }
+
+    // Kernel weights of the input samples around an output sample that
+    // lies `frac` samples after an input sample.
+    fn kernel(&self, frac: f64) -> Vec<f64> {
+        let hw = self.half_width as f64;
+        (0..2 * self.half_width)
+            .map(|k| {
+                let x = frac + hw - 1f64 - k as f64;
+                self.cutoff * sinc(self.cutoff * x) * kaiser(x / hw)
+            })
+            .collect()
+    }
+}
+
+
+/// Convert `samples` from rate `from` to rate `to`.
+pub fn resample(samples: &[f32], from: u32, to: u32) -> Vec<f32> {
+    Resampler::new(from, to).process(samples)
+}
+
+
+fn sinc(x: f64) -> f64 {
+    if x == 0f64 { 1f64 } else { (PI * x).sin() / (PI * x) }
+}
+
+
+// Kaiser window over [-1, 1].
+fn kaiser(x: f64) -> f64 {
+    if x.abs() >= 1f64 {
+        return 0f64;
+    }
+    bessel_i0(KAISER_BETA * (1f64 - x * x).sqrt()) / bessel_i0(KAISER_BETA)
+}
+
+
+// Modified Bessel function of the first kind, order zero.
+fn bessel_i0(x: f64) -> f64 {
+    let (mut sum, mut term, mut k) = (1f64, 1f64, 1f64);
+    while term > 1e-12 * sum {
+        term *= (x / (2f64 * k)).powi(2);
+        sum += term;
+        k += 1f64;
+    }
+    sum
+}
+
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(freq: f64, fps: u32, n: usize) -> Vec<f32> {
+        (0..n).map(|i| (2f64 * PI * freq * i as f64 / fps as f64).sin() as f32).collect()
+    }
+
+    // Largest deviation from `expected`, skipping the edges.
+    fn max_error(x: &[f32], expected: &[f32]) -> f32 {
+        let skip = x.len() / 10;
+        x[skip..x.len() - skip].iter().zip(&expected[skip..])
+            .map(|(a, b)| (a - b).abs())
+            .fold(0f32, f32::max)
+    }
+
+    #[test]
+    fn down_and_up() {
+        let down = resample(&sine(1000f64, 48000, 48000), 48000, 16000);
+        assert_eq!(down.len(), 16000);
+        assert!(max_error(&down, &sine(1000f64, 16000, 16000)) < 1e-3);
+
+        let up = resample(&sine(1000f64, 16000, 16000), 16000, 44100);
+        assert_eq!(up.len(), 44100);
+        assert!(max_error(&up, &sine(1000f64, 44100, 44100)) < 1e-3);
+    }
+
+    #[test]
+    fn removes_aliases() {
+        let down = resample(&sine(12000f64, 48000, 48000), 48000, 16000);
+        assert!(max_error(&down, &[0f32; 16000]) < 1e-3);
+    }
+
+    #[test]
+    fn odd_ratio() {
+        // 44099 phases exceed the table, so kernels are computed per sample
+        let x = sine(440f64, 44100, 4410);
+        let y = resample(&x, 44100, 44099);
+        assert_eq!(y.len(), 4409);
+        assert!(max_error(&y, &sine(440f64, 44099, 4409)) < 1e-3);
+    }
+}
@@ -0,0 +1,313 @@
+//! Recover audio and labels from damaged projects.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use pyo3::prelude::*;
+use rusqlite::Connection;
+
+use crate::audacity::projectdoc::ProjectDoc;
+use crate::audacity::sampleblock::{decode_samples, sample_size};
+use crate::audacity::tagdict::TagDict;
+use crate::db::{self, OpenMode};
+use crate::export::{write_label_file, write_wav};
+use crate::structure::{Label, WaveClip};
+
+
+/// Rate used for the recovered audio if the document does not provide one.
+pub const FALLBACK_RATE: u32 = 44100;
+
+
+#[derive(Debug)]
+pub enum SalvageError {
+    Database(rusqlite::Error),
+    Io(std::io::Error),
+}
+
+impl From<rusqlite::Error> for SalvageError {
+    fn from(err: rusqlite::Error) -> Self {
+        Self::Database(err)
+    }
+}
+
+impl From<std::io::Error> for SalvageError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl std::fmt::Display for SalvageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Database(err) => write!(f, "database error: {}", err),
+            Self::Io(err) => write!(f, "io error: {}", err),
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, Default)]
+#[pyclass]
+pub struct SalvageReport {
+    /// Table the document was recovered from, if any.
+    #[pyo3(get)]
+    pub source: Option<String>,
+
+    /// Whether the document could be decoded to the end.
+    #[pyo3(get)]
+    pub doc_complete: bool,
+
+    #[pyo3(get)]
+    pub rate: u32,
+
+    #[pyo3(get)]
+    pub clips_recovered: usize,
+
+    #[pyo3(get)]
+    pub blocks_recovered: usize,
+
+    /// Block ids referenced by the document but missing or unreadable.
+    #[pyo3(get)]
+    pub blocks_lost: Vec<i64>,
+
+    /// Number of samples replaced by silence.
+    #[pyo3(get)]
+    pub samples_lost: u64,
+
+    #[pyo3(get)]
+    pub labels_recovered: usize,
+
+    #[pyo3(get)]
+    pub files: Vec<String>,
+
+    #[pyo3(get)]
+    pub messages: Vec<String>,
+}
+
+#[pymethods]
+impl SalvageReport {
+    fn __str__(&self) -> String {
+        format!("SalvageReport(source={:?}, doc_complete={}, clips_recovered={}, \
+            blocks_recovered={}, blocks_lost={}, labels_recovered={})",
+            self.source, self.doc_complete, self.clips_recovered, self.blocks_recovered,
+            self.blocks_lost.len(), self.labels_recovered)
+    }
+
+    fn __repr__(&self) -> String {
+        self.__str__()
+    }
+}
+
+
+/// Recover whatever is reachable in the project at `path` into `out_dir`.
+///
+/// The readable part of the document is used to assemble clips. Clip
+/// audio is written to `clip_NNN.wav`, blocks the document does not
+/// reach to `unreferenced.wav` in block id order, and labels to
+/// `labels.txt`. Without a usable document, all blocks are written to
+/// `salvaged.wav` in block id order.
+pub fn salvage(path: &str, out_dir: &str) -> Result<SalvageReport, SalvageError> {
+    fs::create_dir_all(out_dir)?;
+    let con = db::connect(path, OpenMode::ReadOnly)?;
+    let mut report = SalvageReport { rate: FALLBACK_RATE, ..Default::default() };
+
+    let mut clips = Vec::<WaveClip>::new();
+    let mut labels = Vec::<Label>::new();
+    if let Some(mut doc) = recover_doc(&con, &mut report) {
+        match doc.parse_sample_rate() {
+            Some(rate) => report.rate = rate,
+            None => report.messages.push(
+                format!("Sample rate not found, assuming {} Hz", FALLBACK_RATE))
+        }
+
+        match doc.parse_waveclips() {
+            Ok(Some(val)) => clips = val,
+            Ok(None) => {},
+            Err(_) => report.messages.push("Clip structure is damaged".to_string())
+        }
+
+        match doc.parse_labels() {
+            Ok(Some(val)) => labels = val,
+            Ok(None) => {},
+            Err(_) => report.messages.push("Labels are damaged".to_string())
+        }
+    }
+
+    let block_ids = all_block_ids(&con)?;
+    let mut used = HashSet::<i64>::new();
+
+    for (i, clip) in clips.iter().enumerate() {
+        let seq = match &clip.sequences {
+            Some(seq) => seq,
+            None => continue
+        };
+
+        let mut samples = Vec::<f32>::with_capacity(seq.numsamples as usize);
+        for (k, block) in seq.blocks.iter().enumerate() {
+            let id = block.blockid as i64;
+            used.insert(id);
+
+            let expected = match seq.blocks.get(k + 1) {
+                Some(next) => next.start.saturating_sub(block.start),
+                None => (seq.numsamples as usize).saturating_sub(block.start),
+            };
+            match read_block(&con, id) {
+                Some(mut data) => {
+                    data.resize(expected, 0f32);
+                    samples.append(&mut data);
+                    report.blocks_recovered += 1;
+                },
+                None => {
+                    samples.resize(samples.len() + expected, 0f32);
+                    report.blocks_lost.push(id);
+                    report.samples_lost += expected as u64;
+                }
+            }
+        }
+
+        let file = Path::new(out_dir).join(format!("clip_{:03}.wav", i + 1));
+        write_wav(&file.to_string_lossy(), report.rate, &samples)?;
+        report.files.push(file.to_string_lossy().to_string());
+        report.clips_recovered += 1;
+    }
+
+    let remaining: Vec<i64> = block_ids.into_iter().filter(|id| !used.contains(id)).collect();
+    if !remaining.is_empty() {
+        let name = if report.clips_recovered == 0 { "salvaged.wav" } else { "unreferenced.wav" };
+        let mut samples = Vec::<f32>::new();
+        for id in remaining {
+            match read_block(&con, id) {
+                Some(mut data) => {
+                    samples.append(&mut data);
+                    report.blocks_recovered += 1;
+                },
+                None => report.messages.push(format!("Block {} is unreadable", id))
+            }
+        }
+        let file = Path::new(out_dir).join(name);
+        write_wav(&file.to_string_lossy(), report.rate, &samples)?;
+        report.files.push(file.to_string_lossy().to_string());
+    }
+
+    if !labels.is_empty() {
+        let file = Path::new(out_dir).join("labels.txt");
+        write_label_file(&file.to_string_lossy(), &labels)?;
+        report.files.push(file.to_string_lossy().to_string());
+        report.labels_recovered = labels.len();
+    }
+
+    Ok(report)
+}
+
+
+// Decode the readable part of the newest document.
+fn recover_doc(con: &Connection, report: &mut SalvageReport) -> Option<ProjectDoc> {
+    let tables = if db::has_autosave(con) { ["autosave", "project"] } else { ["project", "autosave"] };
+
+    let mut partial: Option<(ProjectDoc, &str)> = None;
+    for table in tables {
+        let mut tagdict = TagDict::new();
+        if !tagdict.decode_partial(con, table) && tagdict.dict.is_empty() {
+            report.messages.push(format!("Dict of table '{}' is unreadable", table));
+            continue;
+        }
+
+        let mut doc = ProjectDoc::new(tagdict);
+        if doc.decode_partial(con, table) {
+            report.source = Some(table.to_string());
+            report.doc_complete = true;
+            return Some(doc);
+        }
+
+        report.messages.push(format!("Doc of table '{}' is damaged", table));
+        if partial.is_none() && !doc.is_empty() {
+            partial = Some((doc, table));
+        }
+    }
+
+    partial.map(|(doc, table)| {
+        report.source = Some(table.to_string());
+        doc
+    })
+}
+
+
+fn all_block_ids(con: &Connection) -> Result<Vec<i64>, rusqlite::Error> {
+    let mut stmt = con.prepare("SELECT blockid FROM sampleblocks ORDER BY blockid")?;
+    let ids = stmt.query_map([], |row| row.get::<_, i64>(0))?;
+    ids.collect()
+}
+
+
+fn read_block(con: &Connection, id: i64) -> Option<Vec<f32>> {
+    let (format, bytes): (i64, Vec<u8>) = con.query_row(
+        "SELECT sampleformat, samples FROM sampleblocks WHERE blockid = ?1", [id],
+        |row| Ok((row.get(0)?, row.get(1)?))).ok()?;
+
+    // Drop a trailing partial sample rather than the whole block.
+    let width = sample_size(format as u64).max(1);
+    let usable = bytes.len() - bytes.len() % width;
+    decode_samples(format as u64, &bytes[..usable])
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::writer::create;
+
+    fn setup(name: &str) -> (String, String) {
+        let dir = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("damaged.aup3");
+        let labels = vec![Label::new(0f64, 1f64, "a".to_string())];
+        create(path.to_str().unwrap(), 8000, &[vec![0.25f32; 300000]], &labels).unwrap();
+        (path.to_str().unwrap().to_string(), dir.join("out").to_str().unwrap().to_string())
+    }
+
+    #[test]
+    fn truncated_doc() {
+        let (path, out) = setup("aup3conv-salvage-truncated");
+        let con = Connection::open(&path).unwrap();
+        con.execute("UPDATE project SET doc = substr(doc, 1, length(doc) - 40)", []).unwrap();
+        drop(con);
+
+        let report = salvage(&path, &out).unwrap();
+        assert!(!report.doc_complete);
+        assert_eq!(report.rate, 8000);
+        assert_eq!(report.clips_recovered, 1);
+        assert_eq!(report.blocks_recovered, 2);
+        assert!(Path::new(&out).join("clip_001.wav").exists());
+    }
+
+    #[test]
+    fn truncated_dict() {
+        // names at the end of the dict are lost, the doc refers to them
+        let (path, out) = setup("aup3conv-salvage-dict");
+        let con = Connection::open(&path).unwrap();
+        con.execute("UPDATE project SET dict = substr(dict, 1, length(dict) - 3)", []).unwrap();
+        drop(con);
+
+        let report = salvage(&path, &out).unwrap();
+        assert!(!report.doc_complete);
+        assert_eq!(report.clips_recovered, 1);
+        assert_eq!(report.blocks_recovered, 2);
+    }
+
+    #[test]
+    fn missing_project_table() {
+        let (path, out) = setup("aup3conv-salvage-missing");
+        let con = Connection::open(&path).unwrap();
+        con.execute("DROP TABLE project", []).unwrap();
+        drop(con);
+
+        let report = salvage(&path, &out).unwrap();
+        assert_eq!(report.source, None);
+        assert_eq!(report.rate, FALLBACK_RATE);
+        assert_eq!(report.blocks_recovered, 2);
+        let size = fs::metadata(Path::new(&out).join("salvaged.wav")).unwrap().len();
+        assert_eq!(size, 44 + 300000 * 4);
+    }
+}
@@ -0,0 +1,185 @@
+//! Summarize many projects in parallel.
+
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+use pyo3::prelude::*;
+
+use crate::analysis::clip_extents;
+use crate::project::Project;
+
+
+/// Number of paths queued ahead of the workers.
+const QUEUE_SIZE: usize = 256;
+
+
+#[derive(Debug, Clone, Default)]
+#[pyclass]
+pub struct ScanResult {
+    #[pyo3(get)]
+    pub path: String,
+
+    #[pyo3(get)]
+    pub rate: Option<u32>,
+
+    /// End of the last clip in seconds.
+    #[pyo3(get)]
+    pub duration: Option<f64>,
+
+    #[pyo3(get)]
+    pub tracks: Option<usize>,
+
+    #[pyo3(get)]
+    pub labels: Option<usize>,
+
+    /// Why the project could not be read.
+    #[pyo3(get)]
+    pub error: Option<String>,
+}
+
+impl ScanResult {
+    fn failed(path: &str, error: String) -> Self {
+        Self { path: path.to_string(), error: Some(error), ..Default::default() }
+    }
+}
+
+#[pymethods]
+impl ScanResult {
+    #[getter]
+    pub fn ok(&self) -> bool {
+        self.error.is_none()
+    }
+
+    fn __str__(&self) -> String {
+        match &self.error {
+            Some(err) => format!("ScanResult(path='{}', error='{}')", self.path, err),
+            None => format!("ScanResult(path='{}', rate={:?}, duration={:?}, tracks={:?}, labels={:?})",
+                self.path, self.rate, self.duration, self.tracks, self.labels)
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        self.__str__()
+    }
+}
+
+
+/// Open the project at `path` and summarize it.
+///
+/// Errors while opening are reported in `error`.
+pub fn summarize(path: &str) -> ScanResult {
+    match Project::open(path) {
+        Ok(project) => {
+            let clips = project.waveclips.as_deref().unwrap_or(&[]);
+            let duration = clip_extents(clips).iter().map(|x| x.1).fold(0f64, f64::max);
+            ScanResult {
+                path: path.to_string(),
+                rate: Some(project.fps),
+                duration: Some(duration),
+                tracks: Some(project.num_tracks),
+                labels: Some(project.labels.as_ref().map_or(0, |x| x.len())),
+                error: None,
+            }
+        },
+        Err(err) => ScanResult::failed(path, err.to_string()),
+    }
+}
+
+
+/// Summarize all files below `root` matching the glob `pattern`.
+///
+/// Files are opened on `workers` threads. Results arrive on the returned
+/// channel in order of completion. Files the pattern matches but that
+/// cannot be listed are reported as failed results.
+pub fn scan(root: &str, pattern: &str, workers: usize) -> Result<mpsc::Receiver<ScanResult>, glob::PatternError> {
+    let full = Path::new(root).join(pattern);
+    let paths = glob::glob(&full.to_string_lossy())?;
+
+    let (path_tx, path_rx) = mpsc::sync_channel::<Result<PathBuf, ScanResult>>(QUEUE_SIZE);
+    let (result_tx, result_rx) = mpsc::channel::<ScanResult>();
+
+    thread::spawn(move || {
+        for entry in paths {
+            let item = entry.map_err(|err|
+                ScanResult::failed(&err.path().to_string_lossy(), err.error().to_string()));
+            if path_tx.send(item).is_err() {
+                break;
+            }
+        }
+    });
+
+    let path_rx = Arc::new(Mutex::new(path_rx));
+    for _ in 0..workers.max(1) {
+        let path_rx = Arc::clone(&path_rx);
+        let result_tx = result_tx.clone();
+        thread::spawn(move || loop {
+            let item = match path_rx.lock().unwrap().recv() {
+                Ok(item) => item,
+                Err(_) => break,
+            };
+            let result = match item {
+                Ok(path) => summarize(&path.to_string_lossy()),
+                Err(failed) => failed,
+            };
+            if result_tx.send(result).is_err() {
+                break;
+            }
+        });
+    }
+    Ok(result_rx)
+}
+
+
+/// Iterator over the results of a running scan.
+#[pyclass]
+pub struct Scan {
+    results: Mutex<mpsc::Receiver<ScanResult>>,
+}
+
+impl Scan {
+    pub fn new(results: mpsc::Receiver<ScanResult>) -> Self {
+        Self { results: Mutex::new(results) }
+    }
+}
+
+#[pymethods]
+impl Scan {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    // Wait for the next result without holding the GIL.
+    fn __next__(&self, py: Python<'_>) -> Option<ScanResult> {
+        py.allow_threads(|| self.results.lock().unwrap().recv().ok())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::writer::create;
+
+    #[test]
+    fn scan_tree() {
+        let root = std::env::temp_dir().join("aup3conv-scan");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("a/b")).unwrap();
+        create(root.join("one.aup3").to_str().unwrap(), 8000, &[vec![0f32; 8000]], &[]).unwrap();
+        create(root.join("a/b/two.aup3").to_str().unwrap(), 8000,
+            &[vec![0f32; 4000], vec![0f32; 16000]], &[]).unwrap();
+        std::fs::write(root.join("a/broken.aup3"), b"not a database").unwrap();
+
+        let mut results: Vec<ScanResult> = scan(root.to_str().unwrap(), "**/*.aup3", 2)
+            .unwrap().into_iter().collect();
+        results.sort_by(|x, y| x.path.cmp(&y.path));
+
+        assert_eq!(results.len(), 3);
+        assert!(results[0].path.ends_with("two.aup3"));
+        assert_eq!(results[0].tracks, Some(2));
+        assert_eq!(results[0].duration, Some(2f64));
+        assert!(results[1].error.is_some());
+        assert_eq!(results[2].rate, Some(8000));
+    }
+}
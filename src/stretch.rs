@@ -0,0 +1,118 @@
+//! Time stretching and pitch shifting of clip audio.
+
+use std::f64::consts::PI;
+
+use crate::resample::resample;
+
+
+/// Length of the analysis window in seconds.
+const WINDOW: f64 = 0.04;
+
+/// Half-width of the range searched for the best matching segment, in
+/// seconds.
+const TOLERANCE: f64 = 0.01;
+
+
+/// Change the duration of `samples` by `ratio` without changing the pitch.
+///
+/// Uses waveform similarity overlap-add (WSOLA): segments of the input
+/// are overlap-added at a fixed synthesis hop, each picked near its
+/// nominal position where it best continues the output so far. The
+/// result has `len * ratio` samples, rounded.
+pub fn time_stretch(samples: &[f32], ratio: f64, fps: u32) -> Vec<f32> {
+    let n_out = (samples.len() as f64 * ratio).round() as usize;
+    if (ratio - 1f64).abs() < 1e-9 || samples.is_empty() {
+        return samples.to_vec();
+    }
+
+    let win = ((WINDOW * fps as f64) as usize).max(4) & !1;
+    let hop = win / 2;
+    let tol = (TOLERANCE * fps as f64) as usize;
+    let window: Vec<f32> = (0..win)
+        .map(|i| (0.5 - 0.5 * (2f64 * PI * i as f64 / win as f64).cos()) as f32)
+        .collect();
+
+    let input = |i: isize| -> f32 {
+        if i >= 0 && (i as usize) < samples.len() { samples[i as usize] } else { 0f32 }
+    };
+
+    let mut out = vec![0f32; n_out + win];
+    let mut prev: isize = 0;
+    let mut k = 0usize;
+    while k * hop < n_out {
+        let nominal = (k as f64 * hop as f64 / ratio).round() as isize;
+        let pos = if k == 0 {
+            0
+        } else {
+            // the natural continuation of the previous segment
+            let target = prev + hop as isize;
+            let mut best = (nominal, f32::MIN);
+            for cand in nominal - tol as isize..=nominal + tol as isize {
+                let score: f32 = (0..hop)
+                    .map(|i| input(cand + i as isize) * input(target + i as isize))
+                    .sum();
+                if score > best.1 {
+                    best = (cand, score);
+                }
+            }
+            best.0
+        };
+
+        let start = k * hop;
+        for i in 0..win {
+            out[start + i] += input(pos + i as isize) * window[i];
+        }
+        prev = pos;
+        k += 1;
+    }
+    out.truncate(n_out);
+    out
+}
+
+
+/// Shift the pitch of `samples` by `cents` without changing the duration.
+pub fn pitch_shift(samples: &[f32], cents: f64, fps: u32) -> Vec<f32> {
+    if cents == 0f64 {
+        return samples.to_vec();
+    }
+    let factor = 2f64.powf(cents / 1200f64);
+    let stretched = time_stretch(samples, factor, fps);
+    let mut out = resample(&stretched, (fps as f64 * factor).round() as u32, fps);
+    out.resize(samples.len(), 0f32);
+    out
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(freq: f64, fps: u32, n: usize) -> Vec<f32> {
+        (0..n).map(|i| (2f64 * PI * freq * i as f64 / fps as f64).sin() as f32).collect()
+    }
+
+    // Estimate the frequency from zero crossings in the middle half.
+    fn frequency(x: &[f32], fps: u32) -> f64 {
+        let x = &x[x.len() / 4..3 * x.len() / 4];
+        let crossings = x.windows(2).filter(|w| w[0] < 0f32 && w[1] >= 0f32).count();
+        crossings as f64 * fps as f64 / x.len() as f64
+    }
+
+    #[test]
+    fn stretch_keeps_pitch() {
+        let x = sine(440f64, 16000, 16000);
+        for ratio in [0.5, 1.5, 2f64] {
+            let y = time_stretch(&x, ratio, 16000);
+            assert_eq!(y.len(), (16000f64 * ratio) as usize);
+            assert!((frequency(&y, 16000) - 440f64).abs() < 5f64, "ratio {}", ratio);
+        }
+    }
+
+    #[test]
+    fn shift_keeps_duration() {
+        let x = sine(440f64, 16000, 16000);
+        let y = pitch_shift(&x, 1200f64, 16000);
+        assert_eq!(y.len(), x.len());
+        assert!((frequency(&y, 16000) - 880f64).abs() < 10f64);
+    }
+}
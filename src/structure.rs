@@ -1,72 +1,500 @@
 use std::io;
+use std::str::FromStr;
+use std::sync::Arc;
 use pyo3::prelude::*;
+use pyo3::exceptions::{PyIOError, PyValueError};
+use pyo3::types::PyDict;
+
+use crate::audacity::audio::AudioError;
+use crate::audacity::decoder::invalid;
+use crate::audacity::sampleblock::decode_samples;
+use crate::db::Pool;
+use crate::midi;
+use crate::resample::resample;
 use crate::tagstack::Tag;
-// pub struct Project {
-//     xmlns: String,
-//     version: String,
-//     audacityversion: String,
-//     sel0: f64,
-//     sel1: f64,
-//     vpos: u8,
-//     h: f64,
-//     zoom: f64,
-//     rate: i32,
-//     snapto: String,
-//     selectionformat: String,
-//     frequencyformat: String,
-//     bandwidthformat: String,
-//     tracks: Vec<Tracks>,
-// }
+use crate::utils::{time_to_frame, time_to_frame_with, Rounding};
+/// Attributes of the `project` element: file format, saved selection
+/// and view state.
+#[derive(Debug, Clone, Default)]
+#[pyclass]
+pub struct ProjectAttributes {
+    #[pyo3(get)]
+    pub xmlns: Option<String>,
+
+    /// Version of the project file format.
+    #[pyo3(get)]
+    pub version: Option<String>,
+
+    /// Version of Audacity that saved the project.
+    #[pyo3(get)]
+    pub audacityversion: Option<String>,
+
+    /// Start of the selection in seconds.
+    #[pyo3(get)]
+    pub sel0: f64,
+
+    /// End of the selection in seconds.
+    #[pyo3(get)]
+    pub sel1: f64,
+
+    /// Lower edge of the spectral selection in Hz, if any.
+    #[pyo3(get)]
+    pub sel_low: Option<f64>,
+
+    /// Upper edge of the spectral selection in Hz, if any.
+    #[pyo3(get)]
+    pub sel_high: Option<f64>,
+
+    /// Vertical scroll position of the track panel.
+    #[pyo3(get)]
+    pub vpos: i32,
+
+    /// Time at the left edge of the track panel in seconds.
+    #[pyo3(get)]
+    pub h: f64,
+
+    /// Zoom in pixels per second.
+    #[pyo3(get)]
+    pub zoom: f64,
+
+    #[pyo3(get)]
+    pub rate: f64,
+
+    #[pyo3(get)]
+    pub snapto: Option<String>,
+
+    #[pyo3(get)]
+    pub selectionformat: Option<String>,
+
+    #[pyo3(get)]
+    pub frequencyformat: Option<String>,
+
+    #[pyo3(get)]
+    pub bandwidthformat: Option<String>,
+}
+
+impl ProjectAttributes {
+    pub fn from_tag(tag: &Tag) -> io::Result<Self> {
+        let text = |key: &str| tag.attributes.get(key).cloned();
+        let num = |key: &str| tag.attributes.get(key).and_then(|val| val.parse::<f64>().ok());
+        let rate = num("rate")
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Key 'rate' not in tag attributes"))?;
+
+        Ok(Self {
+            xmlns: text("xmlns"),
+            version: text("version"),
+            audacityversion: text("audacityversion"),
+            sel0: num("sel0").unwrap_or(0f64),
+            sel1: num("sel1").unwrap_or(0f64),
+            sel_low: num("selLow").filter(|val| *val >= 0f64),
+            sel_high: num("selHigh").filter(|val| *val >= 0f64),
+            vpos: tag.attributes.get("vpos").and_then(|val| val.parse::<i32>().ok()).unwrap_or(0),
+            h: num("h").unwrap_or(0f64),
+            zoom: num("zoom").unwrap_or(0f64),
+            rate,
+            snapto: text("snapto"),
+            selectionformat: text("selectionformat"),
+            frequencyformat: text("frequencyformat"),
+            bandwidthformat: text("bandwidthformat"),
+        })
+    }
+
+    /// Whether a time range is selected.
+    pub fn has_selection(&self) -> bool {
+        self.sel1 > self.sel0
+    }
+}
+
+#[pymethods]
+impl ProjectAttributes {
+    fn __str__(&self) -> String {
+        format!("ProjectAttributes(version={:?}, audacityversion={:?}, sel0={}, sel1={}, h={}, zoom={}, rate={})",
+            self.version, self.audacityversion, self.sel0, self.sel1, self.h, self.zoom, self.rate)
+    }
+
+    fn __repr__(&self) -> String {
+        self.__str__()
+    }
+}
+
+
+/// Realtime effect on a track or on the master channel (Audacity 3.2+).
+#[derive(Debug, Clone)]
+#[pyclass]
+pub struct RealtimeEffect {
+    /// Plugin identifier, e.g. "Built-in Effect: Reverb".
+    #[pyo3(get)]
+    pub plugin_id: String,
+
+    #[pyo3(get)]
+    pub name: Option<String>,
+
+    #[pyo3(get)]
+    pub version: Option<String>,
+
+    /// Whether the effect was switched on. Effects of a bypassed stack
+    /// count as inactive.
+    #[pyo3(get)]
+    pub active: bool,
+
+    /// Serialized parameters in document order.
+    pub parameters: Vec<(String, String)>,
+}
+
+impl RealtimeEffect {
+    pub fn from_tag(tag: &Tag) -> io::Result<Self> {
+        let plugin_id = tag.attributes.get("id")
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Key 'id' not in tag attributes"))?;
+        Ok(Self {
+            plugin_id: plugin_id.clone(),
+            name: tag.attributes.get("name").cloned(),
+            version: tag.attributes.get("version").cloned(),
+            active: tag.attributes.get("active").is_none_or(|x| parse_flag(x)),
+            parameters: Vec::new(),
+        })
+    }
+}
+
+#[pymethods]
+impl RealtimeEffect {
+    #[getter]
+    fn parameters<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new_bound(py);
+        for (key, value) in self.parameters.iter() {
+            dict.set_item(key, value)?;
+        }
+        Ok(dict)
+    }
+
+    fn __str__(&self) -> String {
+        format!("RealtimeEffect(plugin_id='{}', name={:?}, active={}, parameters={})",
+            self.plugin_id, self.name, self.active, self.parameters.len())
+    }
+
+    fn __repr__(&self) -> String {
+        self.__str__()
+    }
+}
+
+
+/// One channel of a wave track.
+#[derive(Debug, Clone)]
+#[pyclass]
+pub struct WaveTrack {
+    #[pyo3(get)]
+    pub name: String,
+
+    #[pyo3(get)]
+    pub rate: Option<u32>,
+
+    #[pyo3(get)]
+    pub channel: i32,
+
+    /// Whether the next track is the second channel of this one.
+    #[pyo3(get)]
+    pub linked: bool,
+
+    #[pyo3(get)]
+    pub mute: bool,
+
+    #[pyo3(get)]
+    pub solo: bool,
+
+    #[pyo3(get)]
+    pub gain: f64,
+
+    #[pyo3(get)]
+    pub pan: f64,
+
+    /// Realtime effects in processing order.
+    #[pyo3(get)]
+    pub effects: Vec<RealtimeEffect>,
+}
+
+impl WaveTrack {
+    pub fn from_tag(tag: &Tag) -> io::Result<Self> {
+        let num = |key: &str| tag.attributes.get(key).and_then(|val| val.parse::<f64>().ok());
+        let flag = |key: &str| tag.attributes.get(key).is_some_and(|x| parse_flag(x));
+        Ok(Self {
+            name: tag.attributes.get("name").cloned().unwrap_or_default(),
+            rate: num("rate").map(|val| val.round() as u32),
+            channel: tag.attributes.get("channel").and_then(|val| val.parse::<i32>().ok()).unwrap_or(2),
+            linked: flag("linked"),
+            mute: flag("mute"),
+            solo: flag("solo"),
+            gain: num("gain").unwrap_or(1f64),
+            pan: num("pan").unwrap_or(0f64),
+            effects: Vec::new(),
+        })
+    }
+}
+
+#[pymethods]
+impl WaveTrack {
+    fn __str__(&self) -> String {
+        format!("WaveTrack(name='{}', rate={:?}, channel={}, mute={}, effects={})",
+            self.name, self.rate, self.channel, self.mute, self.effects.len())
+    }
+
+    fn __repr__(&self) -> String {
+        self.__str__()
+    }
+}
+
+
+/// Time track, whose envelope sets the playback speed along the timeline.
+#[derive(Debug, Clone)]
+#[pyclass]
+pub struct TimeTrack {
+    #[pyo3(get)]
+    pub name: String,
+
+    /// Lowest speed of the envelope's display range.
+    #[pyo3(get)]
+    pub range_lower: f64,
+
+    /// Highest speed of the envelope's display range.
+    #[pyo3(get)]
+    pub range_upper: f64,
+
+    /// Whether the speed is interpolated linearly in its logarithm.
+    #[pyo3(get)]
+    pub interpolate_log: bool,
+
+    /// Control points (time, speed) of the envelope, sorted by time.
+    #[pyo3(get)]
+    pub points: Vec<(f64, f64)>,
+}
+
+impl TimeTrack {
+    pub fn from_tag(tag: &Tag) -> io::Result<Self> {
+        let num = |key: &str, default: f64| tag.attributes.get(key)
+            .and_then(|val| val.parse::<f64>().ok()).unwrap_or(default);
+        Ok(Self {
+            name: tag.attributes.get("name").cloned().unwrap_or_default(),
+            range_lower: num("rangelower", 0.9),
+            range_upper: num("rangeupper", 1.1),
+            interpolate_log: tag.attributes.get("interpolatelog").is_some_and(|x| parse_flag(x)),
+            points: Vec::new(),
+        })
+    }
+}
+
+#[pymethods]
+impl TimeTrack {
+    fn __str__(&self) -> String {
+        format!("TimeTrack(name='{}', points={})", self.name, self.points.len())
+    }
+
+    fn __repr__(&self) -> String {
+        self.__str__()
+    }
+}
+
+
+/// Allegro note track.
+#[derive(Debug, Clone)]
+#[pyclass]
+pub struct NoteTrack {
+    #[pyo3(get)]
+    pub name: String,
+
+    /// Start of the sequence on the project timeline in seconds.
+    #[pyo3(get)]
+    pub offset: f64,
+
+    /// Serialized Allegro sequence.
+    #[pyo3(get)]
+    pub data: String,
+}
+
+impl NoteTrack {
+    pub fn from_tag(tag: &Tag) -> io::Result<Self> {
+        Ok(Self {
+            name: tag.attributes.get("name").cloned().unwrap_or_default(),
+            offset: tag.attributes.get("offset").and_then(|val| val.parse::<f64>().ok()).unwrap_or(0f64),
+            data: tag.attributes.get("data").cloned().unwrap_or_default(),
+        })
+    }
+
+    pub fn write_midi(&self, path: &str) -> io::Result<()> {
+        midi::write_smf(path, &midi::parse_allegro(&self.data), self.offset)
+    }
+}
+
+#[pymethods]
+impl NoteTrack {
+    /// Write the notes to a Standard MIDI File, with times relative to
+    /// the start of the project.
+    pub fn export_midi(&self, path: &str) -> PyResult<()> {
+        self.write_midi(path)
+            .map_err(|err| PyIOError::new_err(format!("Could not write '{}': {}", path, err)))
+    }
+
+    #[getter]
+    pub fn num_notes(&self) -> usize {
+        midi::parse_allegro(&self.data).iter()
+            .flat_map(|track| track.events.iter())
+            .filter(|event| matches!(event, midi::MidiEvent::Note { .. }))
+            .count()
+    }
+
+    fn __str__(&self) -> String {
+        format!("NoteTrack(name='{}', offset={})", self.name, self.offset)
+    }
+
+    fn __repr__(&self) -> String {
+        self.__str__()
+    }
+}
+
+
+// Value of the attribute `key` of `tag`, which must be present.
+fn required<T: FromStr>(tag: &Tag, key: &str) -> io::Result<T> {
+    let value = tag.attributes.get(key)
+        .ok_or_else(|| invalid(format!("Key '{}' not in {} attributes", key, tag.name)))?;
+    value.parse::<T>()
+        .map_err(|_| invalid(format!("Bad value '{}' of {} attribute '{}'", value, tag.name, key)))
+}
+
+
+// Audacity writes flags as Bool fields ("true") or as integers ("1").
+pub(crate) fn parse_flag(value: &str) -> bool {
+    value == "true" || value.parse::<i64>().is_ok_and(|x| x != 0)
+}
 
 
-// pub struct Effects {
-//     active: bool,
-// }
-//
-// enum Tracks {
-//     WaveTrack(WaveTrack),
-//     LabelTrack(LabelTrack),
-// }
-//
-// pub struct WaveTrack {
-//     name: String,
-//     isSelected: bool,
-//     height: i16,
-//     minimized: bool,
-//     channel: u8,
-//     linked: u8,
-//     mute: bool,
-//     solo: bool,
-//     rate: i32,
-//     gain: f64,
-//     pan: f64,
-//     colorindex: i32,
-//     sampleformat: i64,
-//     clips: Vec<WaveClip>,
-// }
-//
 #[derive(Debug, Clone)]
 #[pyclass]
 pub struct WaveClip {
+    /// Start of the sequence, before trimming, in seconds.
     #[pyo3(get)]
     pub offset: f64,
-    trim_left: Option<f64>,
-    trim_right: Option<f64>,
-    name: Option<String>,
-    colorindex: Option<i32>,
+
+    /// Hidden audio at the start of the clip in seconds (Audacity 3.1+).
+    #[pyo3(get)]
+    pub trim_left: f64,
+
+    /// Hidden audio at the end of the clip in seconds (Audacity 3.1+).
+    #[pyo3(get)]
+    pub trim_right: f64,
+
+    #[pyo3(get)]
+    pub name: Option<String>,
+
+    #[pyo3(get)]
+    pub colorindex: Option<i32>,
+
     #[pyo3(get)]
-    pub sequences: Option<Sequence>
+    pub sequences: Option<Sequence>,
     //envelope: Option<Envelope>,
+
+    /// Sample rate of the wave track holding the clip. `Project` sets the
+    /// project rate if the track has none.
+    #[pyo3(get)]
+    pub rate: Option<u32>,
+
+    /// Factor by which playback lengthens the clip (Audacity 3.4+).
+    #[pyo3(get)]
+    pub stretch_ratio: f64,
+
+    /// Pitch shift in cents (Audacity 3.4+).
+    #[pyo3(get)]
+    pub cent_shift: i32,
+
+    /// Tempo of the raw audio in BPM, if the clip was stretched to a tempo.
+    #[pyo3(get)]
+    pub raw_audio_tempo: Option<f64>,
+
+    /// Audacity's pitch and speed preset, 0 for the default algorithm.
+    #[pyo3(get)]
+    pub pitch_and_speed_preset: Option<i32>,
+
+    /// Connections to the project file the clip was read from.
+    pub(crate) pool: Option<Arc<Pool>>,
 }
 
 impl WaveClip {
     pub fn from_tag(tag: &Tag) -> io::Result<Self> {
-        let offset = tag.attributes.get("offset")
-            .expect("Key 'offset' not in tag attributes")
-            .parse::<f64>().unwrap();
+        let offset = required::<f64>(tag, "offset")?;
+
+        let stretch_ratio = tag.attributes.get("clipStretchRatio")
+            .and_then(|val| val.parse::<f64>().ok())
+            .filter(|val| *val > 0f64)
+            .unwrap_or(1f64);
+        let cent_shift = tag.attributes.get("centShift")
+            .and_then(|val| val.parse::<i32>().ok())
+            .unwrap_or(0);
+        let raw_audio_tempo = tag.attributes.get("rawAudioTempo")
+            .and_then(|val| val.parse::<f64>().ok());
+        let pitch_and_speed_preset = tag.attributes.get("pitchAndSpeedPreset")
+            .and_then(|val| val.parse::<i32>().ok());
+        let trim = |key: &str| tag.attributes.get(key)
+            .and_then(|val| val.parse::<f64>().ok())
+            .unwrap_or(0f64);
+
+        Ok(Self { offset: offset, trim_left: trim("trimLeft"), trim_right: trim("trimRight"),
+            name: tag.attributes.get("name").cloned(),
+            colorindex: tag.attributes.get("colorindex").and_then(|val| val.parse::<i32>().ok()),
+            sequences: None, rate: None,
+            stretch_ratio, cent_shift, raw_audio_tempo, pitch_and_speed_preset, pool: None })
+    }
+
+    /// Read the samples of the clip, without trims if `trimmed`.
+    pub fn read_samples(&self, trimmed: bool) -> Result<Vec<f32>, AudioError> {
+        let (pool, seq) = match (&self.pool, &self.sequences) {
+            (Some(pool), Some(seq)) => (pool, seq),
+            _ => return Err(AudioError::NoWaveblocks)
+        };
+        let con = pool.get().map_err(|_| AudioError::ReadFailed)?;
+        let mut out = Vec::<f32>::with_capacity(seq.numsamples as usize);
+        for block in seq.blocks.iter() {
+            let bytes: Vec<u8> = con.query_row("SELECT samples FROM sampleblocks WHERE blockid = ?1",
+                [block.blockid as i64], |row| row.get(0)).map_err(|_| AudioError::ReadFailed)?;
+            let mut samples = decode_samples(seq.sampleformat, &bytes).ok_or(AudioError::ReadFailed)?;
+            out.append(&mut samples);
+        }
+        out.truncate(seq.numsamples as usize);
+
+        if trimmed {
+            let (start, stop) = self.play_frames(self.rate.unwrap_or(0));
+            out.truncate(stop as usize);
+            out.drain(..start as usize);
+        }
+        Ok(out)
+    }
+
+    /// First and end frame of the audible part, counted from the first
+    /// sample of the clip at its rate, or `fps` if its track has none.
+    pub fn play_frames(&self, fps: u32) -> (u64, u64) {
+        let numsamples = self.sequences.as_ref().map_or(0, |seq| seq.numsamples);
+        let frames = |trim: f64| time_to_frame(trim / self.stretch_ratio, self.rate_or(fps)).min(numsamples);
+        let start = frames(self.trim_left);
+        (start, (numsamples - frames(self.trim_right)).max(start))
+    }
+
+    // Length of the untrimmed clip on the timeline in seconds.
+    fn full_duration(&self) -> f64 {
+        match (self.rate, &self.sequences) {
+            (Some(rate), Some(seq)) => seq.numsamples as f64 / rate as f64 * self.stretch_ratio,
+            _ => 0f64
+        }
+    }
+
+    /// Whether playback differs from the raw samples.
+    pub fn is_stretched(&self) -> bool {
+        self.stretch_ratio != 1f64 || self.cent_shift != 0
+    }
 
-        Ok(Self { offset: offset, trim_left: None, trim_right: None,
-            name: None, colorindex: None, sequences: None })
+    /// Audible length of the clip in seconds at rate `fps`.
+    pub fn audible_duration(&self, fps: u32) -> f64 {
+        let numsamples = self.sequences.as_ref().map_or(0, |seq| seq.numsamples);
+        numsamples as f64 / self.rate_or(fps) as f64 * self.stretch_ratio
+    }
+
+    /// Rate of the clip, `fps` if its track has none.
+    pub fn rate_or(&self, fps: u32) -> u32 {
+        self.rate.unwrap_or(fps)
     }
 
     pub fn is_empty(&self) -> bool {
@@ -80,10 +508,41 @@ impl WaveClip {
 
 #[pymethods]
 impl WaveClip {
+    /// Start of the audible part on the timeline in seconds.
+    #[getter]
+    pub fn play_start(&self) -> f64 {
+        self.offset + self.trim_left
+    }
+
+    /// End of the audible part on the timeline in seconds.
+    #[getter]
+    pub fn play_end(&self) -> f64 {
+        self.offset + self.full_duration() - self.trim_right
+    }
+
+    /// Length of the audible part in seconds.
+    #[getter]
+    pub fn duration(&self) -> f64 {
+        self.play_end() - self.play_start()
+    }
+
+    /// Load the samples of the clip, at the rate of its track. With
+    /// `trimmed`, only the audible part between `play_start` and
+    /// `play_end` is returned.
+    #[pyo3(signature = (trimmed=true, target_rate=None))]
+    fn load_audio(&self, py: Python<'_>, trimmed: bool, target_rate: Option<u32>) -> PyResult<Vec<f32>> {
+        let samples = py.allow_threads(|| self.read_samples(trimmed))
+            .map_err(|_| PyIOError::new_err("Could not read audio"))?;
+        match (target_rate, self.rate) {
+            (Some(target), Some(rate)) => Ok(py.allow_threads(|| resample(&samples, rate, target))),
+            _ => Ok(samples)
+        }
+    }
+
     fn __str__(&self) -> String {
-        format!("WaveClip(offset={}, trim_left={:?}, trim_right={:?},
+        format!("WaveClip(offset={}, rate={:?}, stretch_ratio={}, cent_shift={}, trim_left={:?}, trim_right={:?}, \
             name={:?}, colorindex={:?}, sequences={:?})",
-        self.offset, self.trim_left, self.trim_right, self.name, self.colorindex,
+        self.offset, self.rate, self.stretch_ratio, self.cent_shift, self.trim_left, self.trim_right, self.name, self.colorindex,
         self.sequences)
     }
 
@@ -105,17 +564,11 @@ pub struct Sequence {
 
 impl Sequence {
     pub fn from_tag(tag: &Tag) -> io::Result<Self> {
-        let maxsamples = tag.attributes.get("maxsamples")
-            .expect("Key 'start' not in tag attributes")
-            .parse::<u64>().unwrap();
+        let maxsamples = required::<u64>(tag, "maxsamples")?;
 
-        let sampleformat = tag.attributes.get("sampleformat")
-            .expect("Key 'start' not in tag attributes")
-            .parse::<u64>().unwrap();
+        let sampleformat = required::<u64>(tag, "sampleformat")?;
 
-        let numsamples = tag.attributes.get("numsamples")
-            .expect("Key 'start' not in tag attributes")
-            .parse::<u64>().unwrap();
+        let numsamples = required::<u64>(tag, "numsamples")?;
 
         Ok(Self { maxsamples: maxsamples, sampleformat: sampleformat,
             numsamples: numsamples, blocks: Vec::<WaveBlock>::new() })
@@ -150,12 +603,8 @@ pub struct WaveBlock {
 
 impl WaveBlock {
     pub fn from_tag(tag: &Tag) -> io::Result<Self> {
-        let start = tag.attributes.get("start")
-            .expect("Key 'start' not in tag attributes")
-            .parse::<usize>().unwrap();
-        let bid = tag.attributes.get("blockid")
-            .expect("Key 'blockid' not in tag attributes")
-            .parse::<u16>().unwrap();
+        let start = required::<usize>(tag, "start")?;
+        let bid = required::<u16>(tag, "blockid")?;
         Ok(Self { start: start, blockid: bid })
 
     }
@@ -175,15 +624,47 @@ impl WaveBlock{
 // pub struct Envelope {
 //     numpoints: u64,
 // }
-//
-// pub struct LabelTrack {
-//     name: String,
-//     isSelected: bool,
-//     height: i16,
-//     minimized: bool,
-//     numlabels: i32,
-//     labels: Vec<Label>,
-// }
+
+#[derive(Debug, Clone)]
+#[pyclass]
+pub struct LabelTrack {
+    #[pyo3(get)]
+    pub name: String,
+
+    #[pyo3(get)]
+    pub is_selected: bool,
+
+    #[pyo3(get)]
+    pub minimized: bool,
+
+    #[pyo3(get)]
+    pub labels: Vec<Label>,
+}
+
+impl LabelTrack {
+    pub fn from_tag(tag: &Tag) -> io::Result<Self> {
+        let name = required::<String>(tag, "name")?;
+        let is_selected = tag.attributes.get("isSelected").is_some_and(|x| x == "true");
+        let minimized = tag.attributes.get("minimized").is_some_and(|x| x == "true");
+        Ok(Self { name, is_selected, minimized, labels: Vec::<Label>::new() })
+    }
+}
+
+#[pymethods]
+impl LabelTrack {
+    fn __str__(&self) -> String {
+        format!("LabelTrack(name='{}', is_selected={}, labels={})",
+            self.name, self.is_selected, self.labels.len())
+    }
+
+    fn __repr__(&self) -> String {
+        self.__str__()
+    }
+
+    fn __len__(&self) -> usize {
+        self.labels.len()
+    }
+}
 
 #[derive(Debug)]
 #[derive(Clone)]
@@ -197,26 +678,112 @@ pub struct Label {
 
     #[pyo3(get)]
     pub title: String,
+
+    #[pyo3(get, name="low_freq")]
+    pub f0: Option<f64>,
+
+    #[pyo3(get, name="high_freq")]
+    pub f1: Option<f64>,
+
+    /// Position of the label within its track.
+    #[pyo3(get)]
+    pub index: Option<usize>,
+
+    /// Name of the label track holding the label.
+    #[pyo3(get)]
+    pub track: Option<String>,
 }
 
 impl Label {
+    pub fn new(t: f64, t1: f64, title: String) -> Self {
+        Self { t, t1, title, f0: None, f1: None, index: None, track: None }
+    }
+
+    pub fn in_track(mut self, track: Option<String>, index: usize) -> Self {
+        self.track = track;
+        self.index = Some(index);
+        self
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.t1 - self.t
+    }
+
+    // Point labels mark an instant, region labels a time span.
+    pub fn is_point(&self) -> bool {
+        self.t == self.t1
+    }
+
+    pub fn with_band(mut self, f0: Option<f64>, f1: Option<f64>) -> Self {
+        self.f0 = f0;
+        self.f1 = f1;
+        self
+    }
+
+    pub fn has_band(&self) -> bool {
+        self.f0.is_some() || self.f1.is_some()
+    }
+
+    /// Start and stop frame of the label at `fps`.
+    pub fn to_frames(&self, fps: u32, rounding: Rounding) -> (u64, u64) {
+        (time_to_frame_with(self.t, fps, rounding), time_to_frame_with(self.t1, fps, rounding))
+    }
+
     pub fn from_tag(tag: &Tag) -> io::Result<Self> {
-        let title = tag.attributes.get("title")
-            .expect("Key 'title' not in tag attributes");
-        let t = tag.attributes.get("t")
-            .expect("Key 't' not in tag attributes")
-            .parse::<f64>().unwrap();
-        let t1 = tag.attributes.get("t1")
-            .expect("Key 't1' not in tag attributes")
-            .parse::<f64>().unwrap();
-        Ok(Self { title: title.clone(), t: t, t1: t1 })
+        let title = required::<String>(tag, "title")?;
+        let t = required::<f64>(tag, "t")?;
+        let t1 = required::<f64>(tag, "t1")?;
+        let f0 = parse_frequency(tag, &["low", "selLow"]);
+        let f1 = parse_frequency(tag, &["high", "selHigh"]);
+        Ok(Self { title, t: t, t1: t1, f0, f1, index: None, track: None })
     }
 }
 
+// Read the first of `keys` present in `tag` as a frequency. Audacity uses
+// negative values for undefined frequencies.
+fn parse_frequency(tag: &Tag, keys: &[&str]) -> Option<f64> {
+    keys.iter()
+        .find_map(|key| tag.attributes.get(*key))
+        .and_then(|val| val.parse::<f64>().ok())
+        .filter(|val| *val >= 0f64)
+}
+
 #[pymethods]
 impl Label {
+    #[new]
+    #[pyo3(signature = (start, stop, title, low_freq=None, high_freq=None))]
+    fn py_new(start: f64, stop: f64, title: String, low_freq: Option<f64>,
+        high_freq: Option<f64>) -> Self {
+        Self::new(start, stop, title).with_band(low_freq, high_freq)
+    }
+
+    #[getter(duration)]
+    fn py_duration(&self) -> f64 {
+        self.duration()
+    }
+
+    #[getter(is_point)]
+    fn py_is_point(&self) -> bool {
+        self.is_point()
+    }
+
+    // Start and stop frame at `rate`, rounded by "floor", "ceil" or
+    // "nearest".
+    #[pyo3(name = "frames", signature = (rate, rounding="nearest"))]
+    fn py_frames(&self, rate: u32, rounding: &str) -> PyResult<(u64, u64)> {
+        match Rounding::parse(rounding) {
+            Some(rounding) => Ok(self.to_frames(rate, rounding)),
+            None => Err(PyValueError::new_err(format!("Unknown rounding '{}'.", rounding)))
+        }
+    }
+
     fn __str__(&self) -> String {
-        format!("Label(title='{}', start={}, stop={})", self.title, self.t, self.t1)
+        if self.has_band() {
+            format!("Label(title='{}', start={}, stop={}, low_freq={:?}, high_freq={:?})",
+                self.title, self.t, self.t1, self.f0, self.f1)
+        } else {
+            format!("Label(title='{}', start={}, stop={})", self.title, self.t, self.t1)
+        }
     }
 
     fn __repr__(&self) -> String {
@@ -0,0 +1,249 @@
+//! Tabular views of labels and project structure.
+
+use pyo3::prelude::*;
+use pyo3::exceptions::PyValueError;
+use pyo3::types::PyDict;
+
+use crate::structure::{Label, WaveClip};
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Column {
+    Str(Vec<Option<String>>),
+    Int(Vec<Option<i64>>),
+    Float(Vec<Option<f64>>),
+}
+
+
+impl Column {
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Str(values) => values.len(),
+            Self::Int(values) => values.len(),
+            Self::Float(values) => values.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+
+/// Named columns of equal length.
+#[derive(Debug, Clone)]
+pub struct Table {
+    pub columns: Vec<(&'static str, Column)>,
+}
+
+
+impl Table {
+    pub fn num_rows(&self) -> usize {
+        self.columns.first().map_or(0, |(_, col)| col.len())
+    }
+
+    pub fn column(&self, name: &str) -> Option<&Column> {
+        self.columns.iter().find(|(n, _)| *n == name).map(|(_, col)| col)
+    }
+
+    /// Convert to a dict mapping column names to lists.
+    pub fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new_bound(py);
+        for (name, col) in &self.columns {
+            match col {
+                Column::Str(values) => dict.set_item(name, values)?,
+                Column::Int(values) => dict.set_item(name, values)?,
+                Column::Float(values) => dict.set_item(name, values)?,
+            }
+        }
+        Ok(dict)
+    }
+
+    /// Convert to a pandas DataFrame or a pyarrow Table.
+    ///
+    /// `backend` is "pandas" or "arrow". Without a backend, pandas is
+    /// used if it is installed and pyarrow otherwise.
+    pub fn to_python(&self, py: Python<'_>, backend: Option<&str>) -> PyResult<PyObject> {
+        let dict = self.to_dict(py)?;
+        let backend = match backend {
+            Some(name) => name.to_string(),
+            None if py.import_bound("pandas").is_ok() => "pandas".to_string(),
+            None => "arrow".to_string(),
+        };
+
+        match backend.as_str() {
+            "pandas" => Ok(py.import_bound("pandas")?.getattr("DataFrame")?.call1((dict,))?.unbind()),
+            "arrow" => Ok(py.import_bound("pyarrow")?.getattr("table")?.call1((dict,))?.unbind()),
+            other => Err(PyValueError::new_err(format!("Unknown table backend '{}'.", other)))
+        }
+    }
+}
+
+
+/// One row per label.
+pub fn labels_table(labels: &[Label]) -> Table {
+    Table { columns: vec![
+        ("track", Column::Str(labels.iter().map(|x| x.track.clone()).collect())),
+        ("index", Column::Int(labels.iter().map(|x| x.index.map(|i| i as i64)).collect())),
+        ("title", Column::Str(labels.iter().map(|x| Some(x.title.clone())).collect())),
+        ("start", Column::Float(labels.iter().map(|x| Some(x.t)).collect())),
+        ("stop", Column::Float(labels.iter().map(|x| Some(x.t1)).collect())),
+        ("duration", Column::Float(labels.iter().map(|x| Some(x.duration())).collect())),
+        ("low_freq", Column::Float(labels.iter().map(|x| x.f0).collect())),
+        ("high_freq", Column::Float(labels.iter().map(|x| x.f1).collect())),
+    ]}
+}
+
+
+/// One row per clip.
+pub fn clips_table(clips: &[WaveClip], fps: u32) -> Table {
+    let numsamples: Vec<Option<i64>> = clips.iter()
+        .map(|x| x.sequences.as_ref().map(|seq| seq.numsamples as i64)).collect();
+    Table { columns: vec![
+        ("index", Column::Int((0..clips.len() as i64).map(Some).collect())),
+        ("offset", Column::Float(clips.iter().map(|x| Some(x.offset)).collect())),
+        ("rate", Column::Int(clips.iter().map(|x| Some(x.rate_or(fps) as i64)).collect())),
+        ("duration", Column::Float(clips.iter().zip(&numsamples)
+            .map(|(x, n)| n.map(|_| x.audible_duration(fps))).collect())),
+        ("stretch_ratio", Column::Float(clips.iter().map(|x| Some(x.stretch_ratio)).collect())),
+        ("cent_shift", Column::Int(clips.iter().map(|x| Some(x.cent_shift as i64)).collect())),
+        ("numsamples", Column::Int(numsamples)),
+        ("blocks", Column::Int(clips.iter()
+            .map(|x| Some(x.sequences.as_ref().map_or(0, |seq| seq.blocks.len()) as i64)).collect())),
+        ("sampleformat", Column::Int(clips.iter()
+            .map(|x| x.sequences.as_ref().map(|seq| seq.sampleformat as i64)).collect())),
+    ]}
+}
+
+
+/// One row per wave block, with its length in samples.
+pub fn blocks_table(clips: &[WaveClip]) -> Table {
+    let (mut clip, mut index, mut id, mut start, mut length) =
+        (Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new());
+    for (ci, x) in clips.iter().enumerate() {
+        if let Some(seq) = &x.sequences {
+            for (bi, block) in seq.blocks.iter().enumerate() {
+                let stop = seq.blocks.get(bi + 1).map_or(seq.numsamples as usize, |next| next.start);
+                clip.push(Some(ci as i64));
+                index.push(Some(bi as i64));
+                id.push(Some(block.blockid as i64));
+                start.push(Some(block.start as i64));
+                length.push(Some(stop.saturating_sub(block.start) as i64));
+            }
+        }
+    }
+    Table { columns: vec![
+        ("clip_index", Column::Int(clip)),
+        ("block_index", Column::Int(index)),
+        ("block_id", Column::Int(id)),
+        ("start", Column::Int(start)),
+        ("length", Column::Int(length)),
+    ]}
+}
+
+
+#[cfg(feature = "parquet")]
+pub mod parquet {
+    use std::fs::File;
+    use std::sync::Arc;
+
+    use parquet::basic::{ConvertedType, Repetition, Type as PhysicalType};
+    use parquet::data_type::{ByteArray, ByteArrayType, DoubleType, Int64Type};
+    use parquet::errors::Result;
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::writer::SerializedFileWriter;
+    use parquet::schema::types::Type;
+
+    use super::{Column, Table};
+
+    /// Write `table` to a Parquet file with a single row group.
+    pub fn write_parquet(table: &Table, path: &str) -> Result<()> {
+        let mut fields = Vec::new();
+        for (name, col) in &table.columns {
+            let builder = match col {
+                Column::Str(_) => Type::primitive_type_builder(name, PhysicalType::BYTE_ARRAY)
+                    .with_converted_type(ConvertedType::UTF8),
+                Column::Int(_) => Type::primitive_type_builder(name, PhysicalType::INT64),
+                Column::Float(_) => Type::primitive_type_builder(name, PhysicalType::DOUBLE),
+            };
+            fields.push(Arc::new(builder.with_repetition(Repetition::OPTIONAL).build()?));
+        }
+        let schema = Arc::new(Type::group_type_builder("schema").with_fields(fields).build()?);
+
+        let props = Arc::new(WriterProperties::builder().build());
+        let mut writer = SerializedFileWriter::new(File::create(path)?, schema, props)?;
+        let mut group = writer.next_row_group()?;
+        let mut columns = table.columns.iter();
+        while let Some(mut out) = group.next_column()? {
+            let (_, col) = columns.next().expect("Schema and table disagree");
+            match col {
+                Column::Str(values) => {
+                    let (vals, levels) = split(values);
+                    let vals: Vec<ByteArray> = vals.into_iter().map(|x| x.as_str().into()).collect();
+                    out.typed::<ByteArrayType>().write_batch(&vals, Some(&levels), None)?;
+                },
+                Column::Int(values) => {
+                    let (vals, levels) = split(values);
+                    out.typed::<Int64Type>().write_batch(&vals, Some(&levels), None)?;
+                },
+                Column::Float(values) => {
+                    let (vals, levels) = split(values);
+                    out.typed::<DoubleType>().write_batch(&vals, Some(&levels), None)?;
+                },
+            }
+            out.close()?;
+        }
+        group.close()?;
+        writer.close()?;
+        Ok(())
+    }
+
+    // Split optional values into present values and definition levels.
+    fn split<T: Clone>(values: &[Option<T>]) -> (Vec<T>, Vec<i16>) {
+        let present = values.iter().flatten().cloned().collect();
+        let levels = values.iter().map(|x| x.is_some() as i16).collect();
+        (present, levels)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::Project;
+    use crate::writer::create;
+
+    #[test]
+    fn label_columns() {
+        let labels = vec![Label::new(1f64, 3f64, "a".to_string()).with_band(Some(100f64), None)];
+        let table = labels_table(&labels);
+        assert_eq!(table.num_rows(), 1);
+        assert_eq!(table.column("duration"), Some(&Column::Float(vec![Some(2f64)])));
+        assert_eq!(table.column("low_freq"), Some(&Column::Float(vec![Some(100f64)])));
+        assert_eq!(table.column("high_freq"), Some(&Column::Float(vec![None])));
+    }
+
+    #[test]
+    fn block_lengths() {
+        let path = std::env::temp_dir().join("aup3conv-table.aup3");
+        let _ = std::fs::remove_file(&path);
+        let path = path.to_str().unwrap();
+        create(path, 8000, &[vec![0.5f32; 300000]], &[]).unwrap();
+
+        let project = Project::open(path).unwrap();
+        let clips = project.waveclips.as_ref().unwrap();
+        let table = blocks_table(clips);
+        assert_eq!(table.column("length"), Some(&Column::Int(vec![Some(262144), Some(37856)])));
+        assert_eq!(clips_table(clips, 8000).column("duration"), Some(&Column::Float(vec![Some(37.5)])));
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn parquet_file() {
+        let path = std::env::temp_dir().join("aup3conv-labels.parquet");
+        let labels = vec![Label::new(1f64, 3f64, "a".to_string())];
+        parquet::write_parquet(&labels_table(&labels), path.to_str().unwrap()).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!(&bytes[..4], b"PAR1");
+    }
+}
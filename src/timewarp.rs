@@ -0,0 +1,189 @@
+//! Map between the project timeline and playback time of a time track.
+//!
+//! The envelope of a time track gives the playback speed at each point
+//! of the timeline. Playback time is the integral of the inverse speed,
+//! which is what Audacity uses when playing or exporting.
+
+use std::f64::consts::PI;
+
+use crate::structure::TimeTrack;
+
+
+/// Zero crossings of the interpolation kernel on each side, at full
+/// bandwidth.
+const ZERO_CROSSINGS: f64 = 8f64;
+
+/// Output samples between two exactly computed source positions. The
+/// positions in between are interpolated linearly.
+const ANCHOR_STEP: usize = 256;
+
+/// Smallest speed considered, to keep the integral finite.
+const MIN_SPEED: f64 = 1e-6;
+
+
+/// Playback speed at timeline position `t`.
+///
+/// Values are interpolated linearly between control points, or linearly
+/// in the logarithm if the track interpolates logarithmically. Before
+/// the first and after the last point the speed is constant.
+pub fn speed_at(track: &TimeTrack, t: f64) -> f64 {
+    let points = &track.points;
+    let value = match points.iter().position(|(time, _)| *time > t) {
+        None => points.last().map_or(1f64, |p| p.1),
+        Some(0) => points[0].1,
+        Some(i) => {
+            let ((t0, v0), (t1, v1)) = (points[i - 1], points[i]);
+            let x = if t1 > t0 { (t - t0) / (t1 - t0) } else { 1f64 };
+            if track.interpolate_log {
+                v0.max(MIN_SPEED) * (v1.max(MIN_SPEED) / v0.max(MIN_SPEED)).powf(x)
+            } else {
+                v0 + (v1 - v0) * x
+            }
+        }
+    };
+    value.max(MIN_SPEED)
+}
+
+
+/// Playback time at which timeline position `t` is heard.
+pub fn warp_time(track: &TimeTrack, t: f64) -> f64 {
+    if t >= 0f64 { integral(track, 0f64, t) } else { -integral(track, t, 0f64) }
+}
+
+
+/// Timeline position heard at playback time `w`. Inverse of `warp_time`.
+pub fn unwarp_time(track: &TimeTrack, w: f64) -> f64 {
+    // warp_time is strictly increasing, so bisect within a bracket
+    let (mut lo, mut hi) = (0f64, 0f64);
+    let mut step = 1f64;
+    while warp_time(track, hi) < w {
+        lo = hi;
+        hi += step;
+        step *= 2f64;
+    }
+    while warp_time(track, lo) > w {
+        hi = lo;
+        lo -= step;
+        step *= 2f64;
+    }
+    for _ in 0..64 {
+        let mid = 0.5 * (lo + hi);
+        if warp_time(track, mid) < w { lo = mid } else { hi = mid }
+    }
+    0.5 * (lo + hi)
+}
+
+
+// Integral of 1 / speed over [a, b], a <= b, exact per envelope segment.
+fn integral(track: &TimeTrack, a: f64, b: f64) -> f64 {
+    let mut knots = vec![a];
+    knots.extend(track.points.iter().map(|p| p.0).filter(|t| *t > a && *t < b));
+    knots.push(b);
+
+    knots.windows(2).map(|w| {
+        let (t0, t1) = (w[0], w[1]);
+        let (v0, v1) = (speed_at(track, t0), speed_at(track, t1));
+        let width = t1 - t0;
+        if (v1 - v0).abs() < 1e-12 * v0 {
+            width / v0
+        } else if track.interpolate_log {
+            // 1 / v is exponential in t
+            width / v0 * (v0 / v1 - 1f64) / (v0 / v1).ln()
+        } else {
+            width * (v1 / v0).ln() / (v1 - v0)
+        }
+    }).sum()
+}
+
+
+/// Render `samples`, which start at timeline position `start`, as heard
+/// through the time track.
+///
+/// Each output sample is interpolated from the input with a
+/// Blackman-windowed sinc, band-limited to the local speed, so sped up
+/// parts do not alias.
+pub fn render(track: &TimeTrack, samples: &[f32], rate: u32, start: f64) -> Vec<f32> {
+    let fps = rate as f64;
+    let stop = start + samples.len() as f64 / fps;
+    let w0 = warp_time(track, start);
+    let n_out = ((warp_time(track, stop) - w0) * fps).round() as usize;
+
+    let source = |j: usize| (unwarp_time(track, w0 + j as f64 / fps) - start) * fps;
+    let mut out = Vec::<f32>::with_capacity(n_out);
+    let mut anchor = (0usize, source(0));
+    let mut next = (ANCHOR_STEP, source(ANCHOR_STEP));
+    for j in 0..n_out {
+        if j == next.0 {
+            anchor = next;
+            next = (j + ANCHOR_STEP, source(j + ANCHOR_STEP));
+        }
+        let x = (j - anchor.0) as f64 / ANCHOR_STEP as f64;
+        let pos = anchor.1 + (next.1 - anchor.1) * x;
+        let cutoff = (1f64 / speed_at(track, start + pos / fps)).min(1f64);
+        out.push(interpolate(samples, pos, cutoff));
+    }
+    out
+}
+
+
+// Band-limited value of `samples` at fractional index `pos`.
+fn interpolate(samples: &[f32], pos: f64, cutoff: f64) -> f32 {
+    let half_width = ZERO_CROSSINGS / cutoff;
+    let first = (pos - half_width).ceil().max(0f64) as usize;
+    let last = ((pos + half_width).floor() as usize).min(samples.len().saturating_sub(1));
+    let mut acc = 0f64;
+    for (i, val) in samples.iter().enumerate().take(last + 1).skip(first) {
+        let x = pos - i as f64;
+        let arg = PI * cutoff * x;
+        let sinc = if arg == 0f64 { 1f64 } else { arg.sin() / arg };
+        let window = 0.42 + 0.5 * (PI * x / half_width).cos() + 0.08 * (2f64 * PI * x / half_width).cos();
+        acc += *val as f64 * cutoff * sinc * window;
+    }
+    acc as f32
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time_track(points: Vec<(f64, f64)>, interpolate_log: bool) -> TimeTrack {
+        TimeTrack { name: String::new(), range_lower: 0.5, range_upper: 2f64, interpolate_log, points }
+    }
+
+    #[test]
+    fn constant_speed() {
+        let track = time_track(vec![(0f64, 2f64)], false);
+        assert!((warp_time(&track, 3f64) - 1.5).abs() < 1e-12);
+        assert!((unwarp_time(&track, 1.5) - 3f64).abs() < 1e-9);
+        assert_eq!(warp_time(&time_track(vec![], false), 2f64), 2f64);
+    }
+
+    #[test]
+    fn ramp() {
+        // speed from 1 to 2 over 1 s: integral of 1 / (1 + t) is ln 2
+        for log in [false, true] {
+            let track = time_track(vec![(0f64, 1f64), (1f64, 2f64)], log);
+            let expected = if log { 0.5 / 2f64.ln() } else { 2f64.ln() };
+            assert!((warp_time(&track, 1f64) - expected).abs() < 1e-12);
+            assert!((warp_time(&track, 2f64) - expected - 0.5).abs() < 1e-12);
+            for t in [0.25, 0.5, 1.5] {
+                assert!((unwarp_time(&track, warp_time(&track, t)) - t).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn render_speed() {
+        let fps = 8000;
+        let samples: Vec<f32> = (0..16000)
+            .map(|i| (2f64 * PI * 100f64 * i as f64 / fps as f64).sin() as f32).collect();
+        let out = render(&time_track(vec![(0f64, 2f64)], false), &samples, fps, 0f64);
+        assert_eq!(out.len(), 8000);
+        // twice the speed doubles the frequency
+        let expected = |j: usize| (2f64 * PI * 200f64 * j as f64 / fps as f64).sin() as f32;
+        for (j, val) in out.iter().enumerate().take(7900).skip(100) {
+            assert!((val - expected(j)).abs() < 1e-2, "{} {}", val, expected(j));
+        }
+    }
+}
@@ -1,11 +1,54 @@
 //! Common utilities.
 
+/// How to map a time between two frames to a frame index.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rounding {
+    Floor,
+    Ceil,
+    Nearest,
+}
+
+impl Rounding {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "floor" => Some(Self::Floor),
+            "ceil" => Some(Self::Ceil),
+            "nearest" => Some(Self::Nearest),
+            _ => None
+        }
+    }
+}
+
+
+/// Distance in frames below which a frame position counts as exact.
+/// Absorbs the error of `time * fps` for times given in decimal.
+const FRAME_EPSILON: f64 = 1e-6;
+
+
 /// Convert time to frame index.
 ///
 /// Convert a `time` measured in seconds to the corresponding
 /// audio frame index given the samplerate `fps`.
 pub fn time_to_frame(time: f64, fps: u32) -> u64 {
-    (time * fps as f64).round() as u64
+    time_to_frame_with(time, fps, Rounding::Nearest)
+}
+
+
+/// Convert time to frame index using the given `rounding` policy.
+///
+/// Positions within `FRAME_EPSILON` of a frame snap to it before
+/// rounding, so `1.001` s at 16 kHz is frame 16016 with any policy.
+/// Negative times map to frame zero.
+pub fn time_to_frame_with(time: f64, fps: u32, rounding: Rounding) -> u64 {
+    let frame = time * fps as f64;
+    let nearest = frame.round();
+    let frame = match rounding {
+        _ if (frame - nearest).abs() < FRAME_EPSILON => nearest,
+        Rounding::Floor => frame.floor(),
+        Rounding::Ceil => frame.ceil(),
+        Rounding::Nearest => frame.round(),
+    };
+    frame as u64
 }
 
 
@@ -33,6 +76,29 @@ mod tests {
         assert_eq!(time_to_frame(1f64, 44100), 44100);
     }
 
+    #[test]
+    fn ttf_rounding() {
+        assert_eq!(time_to_frame_with(0.00015, 10000, Rounding::Floor), 1);
+        assert_eq!(time_to_frame_with(0.00015, 10000, Rounding::Ceil), 2);
+        assert_eq!(time_to_frame_with(0.00016, 10000, Rounding::Nearest), 2);
+        assert_eq!(time_to_frame_with(-1f64, 10000, Rounding::Ceil), 0);
+        assert_eq!(time_to_frame_with(1.001, 16000, Rounding::Ceil), 16016);
+        assert_eq!(time_to_frame_with(1.1, 100, Rounding::Ceil), 110);
+    }
+
+    #[test]
+    fn ttf_millisecond_grid() {
+        for fps in [16000u32, 44100, 48000] {
+            for ms in 0..5000u64 {
+                let exact = ms * fps as u64;
+                for time in [ms as f64 / 1000f64, ms as f64 * 0.001] {
+                    assert_eq!(time_to_frame_with(time, fps, Rounding::Floor), exact / 1000, "{} {}", fps, time);
+                    assert_eq!(time_to_frame_with(time, fps, Rounding::Ceil), exact.div_ceil(1000), "{} {}", fps, time);
+                }
+            }
+        }
+    }
+
     #[test]
     fn ttb_lower() {
         assert_eq!(time_to_byte(0f64, 44100), 0);
@@ -0,0 +1,284 @@
+//! Create Audacity projects.
+
+use std::path::Path;
+
+use rusqlite::{params, params_from_iter, Connection};
+use rusqlite::types::Value;
+
+use crate::audacity::sampleblock::{SampleBlock, FLOAT_SAMPLE, MAX_BLOCK_SAMPLES};
+use crate::audacity::serializer::ProjectSerializer;
+use crate::structure::Label;
+
+
+/// Magic number Audacity stores as SQLite `application_id` ('AUDY').
+pub const APPLICATION_ID: i32 = 0x41554459;
+
+/// Project format version 3.0.0.0, stored as SQLite `user_version`.
+pub const USER_VERSION: i32 = 0x03000000;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS project
+    (
+        id              INTEGER PRIMARY KEY,
+        dict            BLOB,
+        doc             BLOB
+    );
+    CREATE TABLE IF NOT EXISTS autosave
+    (
+        id              INTEGER PRIMARY KEY,
+        dict            BLOB,
+        doc             BLOB
+    );
+    CREATE TABLE IF NOT EXISTS sampleblocks
+    (
+        blockid         INTEGER PRIMARY KEY AUTOINCREMENT,
+        sampleformat    INTEGER,
+        summin          REAL,
+        summax          REAL,
+        sumrms          REAL,
+        summary256      BLOB,
+        summary64k      BLOB,
+        samples         BLOB
+    );";
+
+
+/// Low-level writer for the tables of an aup3 file.
+pub struct ProjectWriter {
+    con: Connection,
+}
+
+
+impl ProjectWriter {
+
+    /// Create a new, empty project file at `path`.
+    ///
+    /// Existing files are never overwritten.
+    pub fn create(path: &str) -> Result<Self, rusqlite::Error> {
+        if Path::new(path).exists() {
+            return Err(rusqlite::Error::InvalidPath(path.into()));
+        }
+        let con = Connection::open(path)?;
+        con.pragma_update(None, "application_id", APPLICATION_ID)?;
+        con.pragma_update(None, "user_version", USER_VERSION)?;
+        con.execute_batch(SCHEMA)?;
+        Ok(Self { con })
+    }
+
+    /// Insert a new row into `sampleblocks` and return its block id.
+    pub fn write_block(&self, block: &SampleBlock) -> Result<i64, rusqlite::Error> {
+        self.con.execute(
+            "INSERT INTO sampleblocks (sampleformat, summin, summax, sumrms,
+                summary256, summary64k, samples) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![block.sampleformat as i64, block.summary.min, block.summary.max,
+                block.summary.rms, block.summary256, block.summary64k, block.samples])?;
+        Ok(self.con.last_insert_rowid())
+    }
+
+    /// Copy row `block_id` of the `sampleblocks` table of `src` and return
+    /// the id of the new row.
+    pub fn copy_block(&self, src: &Connection, block_id: i64) -> Result<i64, rusqlite::Error> {
+        let mut stmt = src.prepare_cached(
+            "SELECT sampleformat, summin, summax, sumrms, summary256, summary64k, samples
+                FROM sampleblocks WHERE blockid = ?1")?;
+        let values = stmt.query_row([block_id], |row| {
+            (0..7).map(|i| row.get::<_, Value>(i)).collect::<Result<Vec<Value>, _>>()
+        })?;
+
+        self.con.execute(
+            "INSERT INTO sampleblocks (sampleformat, summin, summax, sumrms,
+                summary256, summary64k, samples) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params_from_iter(values))?;
+        Ok(self.con.last_insert_rowid())
+    }
+
+    /// Overwrite the `application_id` and `user_version` of the file.
+    pub fn set_version(&self, application_id: i32, user_version: i32) -> Result<(), rusqlite::Error> {
+        self.con.pragma_update(None, "application_id", application_id)?;
+        self.con.pragma_update(None, "user_version", user_version)
+    }
+
+    /// Rebuild the file to release free pages.
+    pub fn vacuum(&self) -> Result<(), rusqlite::Error> {
+        self.con.execute_batch("VACUUM")
+    }
+
+    /// Write the serialized project document into `project`.
+    pub fn write_doc(&self, dict: &[u8], doc: &[u8]) -> Result<(), rusqlite::Error> {
+        self.con.execute(
+            "INSERT OR REPLACE INTO project (id, dict, doc) VALUES (1, ?1, ?2)",
+            params![dict, doc])?;
+        Ok(())
+    }
+
+    pub fn begin(&self) -> Result<(), rusqlite::Error> {
+        self.con.execute_batch("BEGIN")
+    }
+
+    pub fn commit(&self) -> Result<(), rusqlite::Error> {
+        self.con.execute_batch("COMMIT")
+    }
+}
+
+
+/// Create a project from mono audio tracks and labels.
+///
+/// Each element of `tracks` becomes one wave track with a single clip at
+/// offset zero. Samples are split into blocks of at most
+/// `MAX_BLOCK_SAMPLES`. All labels are placed on one label track.
+pub fn create(path: &str, rate: u32, tracks: &[Vec<f32>], labels: &[Label])
+    -> Result<(), rusqlite::Error> {
+    create_with_rates(path, rate, tracks, &vec![rate; tracks.len()], labels)
+}
+
+
+/// Like `create`, with the sample rate of each track in `track_rates`.
+/// `rate` is the project rate.
+pub fn create_with_rates(path: &str, rate: u32, tracks: &[Vec<f32>], track_rates: &[u32],
+    labels: &[Label]) -> Result<(), rusqlite::Error> {
+
+    let writer = ProjectWriter::create(path)?;
+    writer.begin()?;
+
+    let mut ser = ProjectSerializer::new();
+    write_project_start(&mut ser, rate);
+
+    for (i, (samples, track_rate)) in tracks.iter().zip(track_rates).enumerate() {
+        write_wavetrack_start(&mut ser, &format!("Audio {}", i + 1), *track_rate);
+        write_waveclip_start(&mut ser, 0f64, &format!("Audio {}", i + 1));
+        write_waveclip_audio(&writer, &mut ser, samples)?;
+        ser.end_tag("wavetrack");
+    }
+
+    if !labels.is_empty() {
+        write_labeltrack(&mut ser, "Label 1", labels);
+    }
+
+    ser.end_tag("project");
+
+    let (dict, doc) = ser.finish();
+    writer.write_doc(&dict, &doc)?;
+    writer.commit()
+}
+
+
+pub(crate) fn write_project_start(ser: &mut ProjectSerializer, rate: u32) {
+    ser.start_tag("project");
+    ser.write_str("xmlns", "http://audacity.sourceforge.net/xml/");
+    ser.write_str("version", "1.3.0");
+    ser.write_str("audacityversion", "3.0.0");
+    ser.write_double("sel0", 0f64);
+    ser.write_double("sel1", 0f64);
+    ser.write_int("vpos", 0);
+    ser.write_double("h", 0f64);
+    ser.write_double("zoom", 86.1328125);
+    ser.write_double("rate", rate as f64);
+    ser.write_str("snapto", "off");
+    ser.write_str("selectionformat", "hh:mm:ss + milliseconds");
+    ser.write_str("frequencyformat", "Hz");
+    ser.write_str("bandwidthformat", "octaves");
+}
+
+
+pub(crate) fn write_wavetrack_start(ser: &mut ProjectSerializer, name: &str, rate: u32) {
+    ser.start_tag("wavetrack");
+    ser.write_str("name", name);
+    ser.write_bool("isSelected", false);
+    ser.write_int("height", 150);
+    ser.write_bool("minimized", false);
+    ser.write_int("channel", 2);
+    ser.write_int("linked", 0);
+    ser.write_bool("mute", false);
+    ser.write_bool("solo", false);
+    ser.write_double("rate", rate as f64);
+    ser.write_double("gain", 1f64);
+    ser.write_double("pan", 0f64);
+    ser.write_int("colorindex", 0);
+    ser.write_int("sampleformat", FLOAT_SAMPLE as i32);
+}
+
+
+// Open a waveclip tag. Further clip attributes may follow.
+pub(crate) fn write_waveclip_start(ser: &mut ProjectSerializer, offset: f64, name: &str) {
+    ser.start_tag("waveclip");
+    ser.write_double("offset", offset);
+    ser.write_double("trimLeft", 0f64);
+    ser.write_double("trimRight", 0f64);
+    ser.write_str("name", name);
+    ser.write_int("colorindex", 0);
+}
+
+
+// Write `samples` as the sequence of the open waveclip and close it.
+pub(crate) fn write_waveclip_audio(writer: &ProjectWriter, ser: &mut ProjectSerializer, samples: &[f32])
+    -> Result<(), rusqlite::Error> {
+    ser.start_tag("sequence");
+    ser.write_size_t("maxsamples", MAX_BLOCK_SAMPLES);
+    ser.write_size_t("sampleformat", FLOAT_SAMPLE as usize);
+    ser.write_longlong("numsamples", samples.len() as i64);
+    for (k, chunk) in samples.chunks(MAX_BLOCK_SAMPLES).enumerate() {
+        let block_id = writer.write_block(&SampleBlock::from_samples(chunk))?;
+        ser.start_tag("waveblock");
+        ser.write_longlong("start", (k * MAX_BLOCK_SAMPLES) as i64);
+        ser.write_longlong("blockid", block_id);
+        ser.end_tag("waveblock");
+    }
+    ser.end_tag("sequence");
+
+    ser.start_tag("envelope");
+    ser.write_int("numpoints", 0);
+    ser.end_tag("envelope");
+
+    ser.end_tag("waveclip");
+    Ok(())
+}
+
+
+pub(crate) fn write_labeltrack(ser: &mut ProjectSerializer, name: &str, labels: &[Label]) {
+    ser.start_tag("labeltrack");
+    ser.write_str("name", name);
+    ser.write_bool("isSelected", false);
+    ser.write_int("height", 73);
+    ser.write_bool("minimized", false);
+    ser.write_int("numlabels", labels.len() as i32);
+    for label in labels {
+        ser.start_tag("label");
+        ser.write_double("t", label.t);
+        ser.write_double("t1", label.t1);
+        if label.has_band() {
+            ser.write_double("selLow", label.f0.unwrap_or(-1f64));
+            ser.write_double("selHigh", label.f1.unwrap_or(-1f64));
+        }
+        ser.write_str("title", &label.title);
+        ser.end_tag("label");
+    }
+    ser.end_tag("labeltrack");
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::Project;
+    use crate::audacity::audio::{AudioLoader, AudioProcessor};
+
+    #[test]
+    fn create_and_open() {
+        let path = std::env::temp_dir().join("aup3conv-create-and-open.aup3");
+        let _ = std::fs::remove_file(&path);
+        let path = path.to_str().unwrap();
+
+        let samples: Vec<f32> = (0..300000).map(|i| (i % 100) as f32 / 100f32).collect();
+        let labels = vec![Label::new(1f64, 2f64, "bird".to_string())];
+        create(path, 16000, std::slice::from_ref(&samples), &labels).unwrap();
+
+        let project = Project::open(path).unwrap();
+        assert_eq!(project.fps(), 16000);
+        let parsed = project.labels.as_ref().unwrap();
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].title, "bird");
+
+        let mut out = Vec::<f32>::new();
+        project.load_slice(1f64, 2f64, &mut out).unwrap();
+        assert_eq!(out.as_slice(), &samples[16000..32000]);
+    }
+}
@@ -31,3 +31,214 @@ def test_labels(project) -> None:
 def test_load_audio(project) -> None:
     snd = project.load_audio()
     assert isinstance(snd, list)
+
+def test_create(tmp_path) -> None:
+    path = str(tmp_path / "created.aup3")
+    labels = [ac.Label(0.5, 1.0, "tone")]
+    ac.create(path, 8000, [[0.0] * 16000], labels=labels)
+    project = ac.open(path)
+    assert project.fps == 8000
+    assert [x.title for x in project.labels] == ["tone"]
+
+def test_check(tmp_path) -> None:
+    path = str(tmp_path / "checked.aup3")
+    ac.create(path, 8000, [[0.0] * 8000])
+    report = ac.open(path).check()
+    assert report.ok
+    assert report.issues == []
+
+def test_check_cli(tmp_path, capsys) -> None:
+    from aup3conv.__main__ import main
+
+    path = tmp_path / "bad.aup3"
+    path.write_text("not a project")
+    assert main(["check", str(path)]) == 1
+    assert "cannot open" in capsys.readouterr().out
+
+def test_label_band(tmp_path) -> None:
+    path = str(tmp_path / "band.aup3")
+    labels = [ac.Label(0.0, 0.5, "call", low_freq=200.0, high_freq=900.0)]
+    ac.create(path, 8000, [[0.0] * 8000], labels=labels)
+    project = ac.open(path)
+    label = project.labels[0]
+    assert (label.low_freq, label.high_freq) == (200.0, 900.0)
+    assert len(project.load_label(label, bandpass=True)) == 4000
+
+def test_find_labels(tmp_path) -> None:
+    path = str(tmp_path / "query.aup3")
+    labels = [ac.Label(0.0, 1.0, "owl"), ac.Label(2.0, 2.0, "owl call"), ac.Label(3.0, 6.0, "wind")]
+    ac.create(path, 8000, [[0.0] * 8000], labels=labels)
+    project = ac.open(path)
+    assert [x.title for x in project.find_labels(regex="^owl")] == ["owl", "owl call"]
+    assert [x.title for x in project.find_labels(kind="point")] == ["owl call"]
+    assert [x.index for x in project.find_labels(within=(2.5, 7.0))] == [2]
+
+def test_label_report(tmp_path) -> None:
+    path = str(tmp_path / "report.aup3")
+    labels = [ac.Label(0.0, 0.6, "a"), ac.Label(0.5, 0.8, "b"), ac.Label(0.9, 2.0, "a")]
+    ac.create(path, 8000, [[0.0] * 8000], labels=labels)
+    report = ac.open(path).label_report(vocabulary={"a"})
+    assert [x.kind for x in report.findings] == ["overlap", "unknown_title", "beyond_audio"]
+
+
+def test_labels_table(tmp_path) -> None:
+    pa = pytest.importorskip("pyarrow")
+    path = str(tmp_path / "table.aup3")
+    labels = [ac.Label(0.0, 0.5, "a", low_freq=100.0), ac.Label(0.6, 0.8, "b")]
+    ac.create(path, 8000, [[0.0] * 8000], labels=labels)
+    project = ac.open(path)
+
+    table = project.labels_table(backend="arrow")
+    assert isinstance(table, pa.Table)
+    assert table.column_names == ["track", "index", "title", "start", "stop",
+                                  "duration", "low_freq", "high_freq"]
+    assert table.column("low_freq").to_pylist() == [100.0, None]
+    assert project.blocks_table(backend="arrow").num_rows == 1
+
+    out = tmp_path / "labels.parquet"
+    project.write_parquet(str(out))
+    import pyarrow.parquet as pq
+    assert pq.read_table(out).num_rows == 2
+
+def test_table_backend(tmp_path) -> None:
+    path = str(tmp_path / "backend.aup3")
+    ac.create(path, 8000, [[0.0] * 8000])
+    with pytest.raises(ValueError, match="Unknown table backend"):
+        ac.open(path).labels_table(backend="excel")
+
+
+def test_scan(tmp_path) -> None:
+    ac.create(str(tmp_path / "a.aup3"), 8000, [[0.0] * 8000])
+    (tmp_path / "sub").mkdir()
+    (tmp_path / "sub" / "b.aup3").write_bytes(b"garbage")
+
+    results = sorted(ac.scan(str(tmp_path), workers=2), key=lambda x: x.path)
+    assert [x.ok for x in results] == [True, False]
+    assert results[0].rate == 8000
+    assert results[0].duration == 1.0
+
+
+def test_threaded_loading(tmp_path) -> None:
+    from concurrent.futures import ThreadPoolExecutor
+
+    path = str(tmp_path / "threads.aup3")
+    ac.create(path, 8000, [[0.25] * 80000])
+    project = ac.open(path)
+    with ThreadPoolExecutor(max_workers=4) as pool:
+        chunks = list(pool.map(lambda i: project.load_audio(i, i + 0.5), range(8)))
+    assert all(len(x) == 4000 for x in chunks)
+    assert all(v == 0.25 for x in chunks for v in x)
+
+
+def test_load_many(tmp_path) -> None:
+    path = str(tmp_path / "many.aup3")
+    ac.create(path, 8000, [[float(i % 100) for i in range(80000)]])
+    project = ac.open(path)
+    segments = [(5.0, 6.0), (0.0, 0.5), (2.0, 2.25)]
+    chunks = project.load_many(segments)
+    assert [len(x) for x in chunks] == [8000, 4000, 2000]
+    assert chunks[2] == project.load_audio(2.0, 2.25)
+
+
+def test_load_frames(tmp_path) -> None:
+    path = str(tmp_path / "frames.aup3")
+    ac.create(path, 8000, [[float(i) for i in range(8000)]])
+    project = ac.open(path)
+    assert project.num_frames == 8000
+    assert project.load_frames(100, 3) == [100.0, 101.0, 102.0]
+    with pytest.raises(ValueError):
+        project.load_frames(7999, 2)
+
+    label = ac.Label(0.00015, 0.00035, "x")
+    assert label.frames(10000, rounding="floor") == (1, 3)
+    assert project.label_frames(label, rounding="ceil") == (2, 3)
+
+
+def test_track_rates(tmp_path) -> None:
+    path = str(tmp_path / "rates.aup3")
+    ac.create(path, 8000, [[0.0] * 8000, [0.0] * 32000], track_rates=[8000, 16000])
+    project = ac.open(path)
+    assert project.rates == [8000, 16000]
+    assert len(project.load_audio(0.0, 1.0)) == 16000
+    assert len(project.load_audio(0.0, 1.0, target_rate=8000)) == 8000
+
+
+def test_metadata(tmp_path) -> None:
+    path = str(tmp_path / "tags.aup3")
+    ac.create(path, 8000, [[0.0] * 8000])
+    assert ac.open(path).metadata == {}
+    with pytest.raises(ValueError):
+        ac.open(path).metadata = {"Title": "x"}
+
+    project = ac.open(path, mode="readwrite")
+    project.metadata = {"Title": "session 4", "SubjectID": "S017"}
+    assert ac.open(path).metadata == {"Title": "session 4", "SubjectID": "S017"}
+
+    wav = str(tmp_path / "out.wav")
+    project.export_audio(wav)
+    assert b"S017" in open(wav, "rb").read()
+    flac = str(tmp_path / "out.flac")
+    project.export_audio(flac, 0.0, 0.5)
+    assert open(flac, "rb").read(4) == b"fLaC"
+
+
+def test_project_attributes(tmp_path) -> None:
+    path = str(tmp_path / "attributes.aup3")
+    ac.create(path, 8000, [[0.0] * 8000])
+    project = ac.open(path)
+    assert project.version == "1.3.0"
+    assert project.audacity_version == "3.0.0"
+    assert project.attributes.rate == 8000.0
+    assert project.selection is None
+    with pytest.raises(ValueError):
+        project.load_selection()
+
+
+def test_compatibility(tmp_path) -> None:
+    import sqlite3
+
+    path = str(tmp_path / "compat.aup3")
+    ac.create(path, 8000, [[0.0] * 8000])
+    project = ac.open(path)
+    assert project.format_version == "3.0.0.0"
+    assert project.compatibility.supported
+
+    con = sqlite3.connect(path)
+    con.execute("PRAGMA user_version = 67698688")  # 4.9.0.0
+    con.close()
+    with pytest.warns(UserWarning, match="not supported"):
+        project = ac.open(path)
+    assert not project.compatibility.supported
+
+
+def test_tracks(tmp_path) -> None:
+    path = str(tmp_path / "tracks.aup3")
+    ac.create(path, 8000, [[0.0] * 800, [0.0] * 800])
+    project = ac.open(path)
+    assert len(project.tracks) == project.num_tracks == 2
+    assert project.tracks[0].rate == 8000
+    assert project.tracks[0].effects == []
+    assert project.master_effects == []
+    assert project.note_tracks == []
+
+
+def test_time_warp_without_time_track(tmp_path) -> None:
+    path = str(tmp_path / "warp.aup3")
+    ac.create(path, 8000, [[0.5] * 8000])
+    project = ac.open(path)
+    assert project.time_track is None
+    assert project.warp_time(0.75) == project.unwarp_time(0.75) == 0.75
+    assert project.load_audio(0.0, 0.5, time_warp=True) == project.load_audio(0.0, 0.5)
+
+
+def test_clips(tmp_path) -> None:
+    path = str(tmp_path / "clips.aup3")
+    ac.create(path, 8000, [[0.5] * 800, [0.25] * 400])
+    project = ac.open(path)
+    assert project.clip("Audio 3") is None
+    clip = project.clip("Audio 2")
+    assert clip.name == "Audio 2"
+    assert clip.colorindex == 0
+    assert (clip.play_start, clip.play_end, clip.duration) == (0.0, 0.05, 0.05)
+    assert clip.load_audio() == [0.25] * 400
+    assert abs(len(clip.load_audio(target_rate=16000)) - 800) <= 1